@@ -0,0 +1,131 @@
+use cozy_chess::Square;
+use iced::Color;
+
+/// Colors of Lichess-style board annotations, written as their initial.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkColor {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl MarkColor {
+    fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'G' => Some(MarkColor::Green),
+            'R' => Some(MarkColor::Red),
+            'Y' => Some(MarkColor::Yellow),
+            'B' => Some(MarkColor::Blue),
+            _ => None,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            MarkColor::Green => 'G',
+            MarkColor::Red => 'R',
+            MarkColor::Yellow => 'Y',
+            MarkColor::Blue => 'B',
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            MarkColor::Green => Color::from_rgba(0.1, 0.6, 0.1, 0.8),
+            MarkColor::Red => Color::from_rgba(0.8, 0.1, 0.1, 0.8),
+            MarkColor::Yellow => Color::from_rgba(0.9, 0.7, 0.0, 0.8),
+            MarkColor::Blue => Color::from_rgba(0.1, 0.3, 0.8, 0.8),
+        }
+    }
+}
+
+/// Arrows and circled squares drawn on one position.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotations {
+    pub arrows: Vec<(Square, Square, MarkColor)>,
+    pub marked_squares: Vec<(Square, MarkColor)>,
+}
+
+impl Annotations {
+    /// Reads the `[%cal Ge2e4,Rd1d8]` and `[%csl Ge4]` commands of a PGN
+    /// comment. Malformed entries are skipped.
+    pub fn parse(comment: &str) -> Self {
+        let mut annotations = Annotations::default();
+
+        for command in comment.split("[%").skip(1) {
+            let Some((command, _)) = command.split_once(']') else {
+                continue;
+            };
+            let Some((name, items)) = command.split_once(' ') else {
+                continue;
+            };
+
+            for item in items.split(',').map(str::trim).filter(|item| item.is_ascii()) {
+                let Some(color) = item.chars().next().and_then(MarkColor::from_letter) else {
+                    continue;
+                };
+                match (name, item.len()) {
+                    ("cal", 5) => {
+                        if let (Ok(from), Ok(to)) = (item[1..3].parse(), item[3..5].parse()) {
+                            annotations.arrows.push((from, to, color));
+                        }
+                    },
+                    ("csl", 3) => {
+                        if let Ok(square) = item[1..3].parse() {
+                            annotations.marked_squares.push((square, color));
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        annotations
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arrows.is_empty() && self.marked_squares.is_empty()
+    }
+
+    /// The commands describing these annotations, or `None` when there are none.
+    pub fn to_comment(&self) -> Option<String> {
+        let mut comment = String::new();
+        if !self.marked_squares.is_empty() {
+            let items: Vec<String> = self.marked_squares.iter()
+                .map(|(square, color)| format!("{}{square}", color.letter()))
+                .collect();
+            comment += &format!("[%csl {}]", items.join(","));
+        }
+        if !self.arrows.is_empty() {
+            let items: Vec<String> = self.arrows.iter()
+                .map(|(from, to, color)| format!("{}{from}{to}", color.letter()))
+                .collect();
+            comment += &format!("[%cal {}]", items.join(","));
+        }
+        (!comment.is_empty()).then_some(comment)
+    }
+
+    pub fn extend(&mut self, other: Annotations) {
+        self.arrows.extend(other.arrows);
+        self.marked_squares.extend(other.marked_squares);
+    }
+
+    /// Adds the arrow, or removes it if it was already drawn.
+    pub fn toggle_arrow(&mut self, from: Square, to: Square, color: MarkColor) {
+        let before = self.arrows.len();
+        self.arrows.retain(|&(f, t, _)| (f, t) != (from, to));
+        if self.arrows.len() == before {
+            self.arrows.push((from, to, color));
+        }
+    }
+
+    /// Circles the square, or clears it if it was already circled.
+    pub fn toggle_square(&mut self, square: Square, color: MarkColor) {
+        let before = self.marked_squares.len();
+        self.marked_squares.retain(|&(s, _)| s != square);
+        if self.marked_squares.len() == before {
+            self.marked_squares.push((square, color));
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use cozy_chess::Board;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use crate::bot::BotConfig;
+use crate::game_end::GameEnd;
+use crate::{MATCH_MAX_PLIES, search};
+
+// depth decides when a search stops, this only guards against runaways
+const SEARCH_TIME_LIMIT: Duration = Duration::from_secs(3600);
+
+/// Plays `games` games of the built-in search against itself at a fixed
+/// `depth` and prints how fast it went. Each game opens with book moves
+/// seeded by its number, so runs are reproducible but the games differ.
+pub fn run_selfplay(games: u32, depth: u32) {
+    let bot = BotConfig { depth, ..BotConfig::new(Some(0)) };
+    let mut total_nodes = 0u64;
+    let mut total_moves = 0usize;
+    let started = Instant::now();
+
+    for game in 0..games {
+        let mut rng = StdRng::seed_from_u64(bot.seed + game as u64);
+        let mut board = Board::default();
+        let mut hashes = vec![board.hash()];
+        let mut plies = 0;
+
+        let result = loop {
+            if let Some(end) = GameEnd::detect(&board, &hashes) {
+                break end.result().to_owned();
+            }
+            if plies >= MATCH_MAX_PLIES {
+                break "1/2-1/2".to_owned();
+            }
+
+            let mv = match bot.book_move(&board, &mut rng) {
+                Some(mv) => mv,
+                None => {
+                    let result = search::search(&board, SEARCH_TIME_LIMIT, bot.depth);
+                    total_nodes += result.nodes;
+                    match result.best_move {
+                        Some(mv) => mv,
+                        None => break "*".to_owned(),
+                    }
+                },
+            };
+
+            board.play_unchecked(mv);
+            hashes.push(board.hash());
+            plies += 1;
+        };
+
+        total_moves += plies;
+        println!("game {}: {result} in {plies} plies", game + 1);
+    }
+
+    let elapsed = started.elapsed();
+    println!(
+        "games: {games}, moves: {total_moves}, nodes: {total_nodes}, time: {:.2}s, nodes/sec: {:.0}",
+        elapsed.as_secs_f64(),
+        total_nodes as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+    );
+}
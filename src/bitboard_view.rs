@@ -0,0 +1,79 @@
+use std::fmt;
+
+use cozy_chess::{BitBoard, Board, Color, Piece};
+
+use crate::position;
+
+/// Bitboards the debug overlay can tint on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitboardView {
+    Occupied,
+    WhitePieces,
+    BlackPieces,
+    Pawns,
+    Knights,
+    Bishops,
+    Rooks,
+    Queens,
+    Kings,
+    WhiteAttacks,
+    BlackAttacks,
+    Pinned,
+    Checkers,
+}
+
+impl BitboardView {
+    pub const ALL: [BitboardView; 13] = [
+        BitboardView::Occupied,
+        BitboardView::WhitePieces,
+        BitboardView::BlackPieces,
+        BitboardView::Pawns,
+        BitboardView::Knights,
+        BitboardView::Bishops,
+        BitboardView::Rooks,
+        BitboardView::Queens,
+        BitboardView::Kings,
+        BitboardView::WhiteAttacks,
+        BitboardView::BlackAttacks,
+        BitboardView::Pinned,
+        BitboardView::Checkers,
+    ];
+
+    pub fn of(self, board: &Board) -> BitBoard {
+        match self {
+            BitboardView::Occupied => board.occupied(),
+            BitboardView::WhitePieces => board.colors(Color::White),
+            BitboardView::BlackPieces => board.colors(Color::Black),
+            BitboardView::Pawns => board.pieces(Piece::Pawn),
+            BitboardView::Knights => board.pieces(Piece::Knight),
+            BitboardView::Bishops => board.pieces(Piece::Bishop),
+            BitboardView::Rooks => board.pieces(Piece::Rook),
+            BitboardView::Queens => board.pieces(Piece::Queen),
+            BitboardView::Kings => board.pieces(Piece::King),
+            BitboardView::WhiteAttacks => position::attacked_squares(board, Color::White),
+            BitboardView::BlackAttacks => position::attacked_squares(board, Color::Black),
+            BitboardView::Pinned => board.pinned(),
+            BitboardView::Checkers => board.checkers(),
+        }
+    }
+}
+
+impl fmt::Display for BitboardView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BitboardView::Occupied => "occupied",
+            BitboardView::WhitePieces => "white pieces",
+            BitboardView::BlackPieces => "black pieces",
+            BitboardView::Pawns => "pawns",
+            BitboardView::Knights => "knights",
+            BitboardView::Bishops => "bishops",
+            BitboardView::Rooks => "rooks",
+            BitboardView::Queens => "queens",
+            BitboardView::Kings => "kings",
+            BitboardView::WhiteAttacks => "white attacks",
+            BitboardView::BlackAttacks => "black attacks",
+            BitboardView::Pinned => "pinned",
+            BitboardView::Checkers => "checkers",
+        })
+    }
+}
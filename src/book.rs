@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use cozy_chess::{Board, Move};
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::position;
+
+const BUILTIN_BOOK: &str = include_str!("book.txt");
+
+/// Weighted replies for known opening positions.
+#[derive(Debug, Default)]
+pub struct OpeningBook {
+    entries: HashMap<u64, Vec<(Move, u32)>>,
+}
+
+impl OpeningBook {
+    pub fn builtin() -> Self {
+        OpeningBook::parse(BUILTIN_BOOK).expect("built-in opening book is valid")
+    }
+
+    /// Parses lines of `<uci moves from the initial position> : <reply> <weight>, ...`.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut book = OpeningBook::default();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: &str| format!("line {}: {reason}", number + 1);
+            let (moves, replies) = line.split_once(':').ok_or_else(|| error("expected `<moves> : <replies>`"))?;
+
+            let mut board = Board::default();
+            for uci in moves.split_whitespace() {
+                let mv = position::parse_uci_move(&board, uci).ok_or_else(|| error(&format!("illegal move {uci}")))?;
+                board.play_unchecked(mv);
+            }
+
+            let entry = book.entries.entry(board.hash()).or_default();
+            for reply in replies.split(',') {
+                let (uci, weight) = reply.trim().split_once(' ').ok_or_else(|| error("expected `<reply> <weight>`"))?;
+                let mv = position::parse_uci_move(&board, uci).ok_or_else(|| error(&format!("illegal reply {uci}")))?;
+                let weight = weight.trim().parse().map_err(|_| error(&format!("invalid weight {weight}")))?;
+                entry.push((mv, weight));
+            }
+        }
+
+        Ok(book)
+    }
+
+    /// A book reply for `board` picked according to the weights, or `None`
+    /// once the game has left the book.
+    pub fn pick(&self, board: &Board, rng: &mut impl Rng) -> Option<Move> {
+        let replies = self.entries.get(&board.hash())?;
+        replies.choose_weighted(rng, |(_, weight)| *weight).ok().map(|(mv, _)| *mv)
+    }
+}
@@ -0,0 +1,72 @@
+use cozy_chess::{Board, Move};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::book::OpeningBook;
+use crate::search;
+
+/// How the built-in bot plays, set up once when the app starts.
+#[derive(Debug)]
+pub struct BotConfig {
+    /// Seeds every random choice of the bot, so a game can be played again
+    /// move for move.
+    pub seed: u64,
+    /// Deepest the search goes within its time limit.
+    pub depth: u32,
+    pub book: OpeningBook,
+}
+
+impl BotConfig {
+    /// A fresh random seed is drawn when `seed` is `None`.
+    pub fn new(seed: Option<u64>) -> Self {
+        BotConfig {
+            seed: seed.unwrap_or_else(rand::random),
+            depth: search::MAX_DEPTH,
+            book: OpeningBook::builtin(),
+        }
+    }
+
+    /// The random number generator the bot draws from, starting over from
+    /// the seed each time.
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+
+    /// A book reply for `board`, if it's still in the book. All randomness
+    /// comes from `rng`, so the same generator state gives the same move.
+    pub fn book_move(&self, board: &Board, rng: &mut impl Rng) -> Option<Move> {
+        self.book.pick(board, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Book moves from the initial position until the game leaves the book.
+    fn book_line(config: &BotConfig) -> Vec<Move> {
+        let mut rng = config.rng();
+        let mut board = Board::default();
+        let mut line = Vec::new();
+        while let Some(mv) = config.book_move(&board, &mut rng) {
+            board.play(mv);
+            line.push(mv);
+        }
+        line
+    }
+
+    #[test]
+    fn seed_picks_the_book_move() {
+        let config = BotConfig::new(Some(3));
+        let expected = "d2d4".parse().unwrap();
+        assert_eq!(config.book_move(&Board::default(), &mut config.rng()), Some(expected));
+    }
+
+    #[test]
+    fn same_seed_plays_the_same_line() {
+        let first = BotConfig::new(Some(20240611));
+        let second = BotConfig::new(Some(20240611));
+        assert!(!book_line(&first).is_empty());
+        assert_eq!(book_line(&first), book_line(&second));
+    }
+}
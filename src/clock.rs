@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+use cozy_chess::Color;
+
+/// Time control used when the clock is switched on.
+pub const BASE_TIME: Duration = Duration::from_secs(5 * 60);
+pub const INCREMENT: Duration = Duration::from_secs(3);
+/// Remaining time below which a player is warned.
+pub const LOW_TIME: Duration = Duration::from_secs(10);
+
+/// A chess clock counting down the time of the side to move.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub white_time: Duration,
+    pub black_time: Duration,
+    pub increment: Duration,
+    last_tick: Option<Instant>,
+    /// Whether each side, indexed by color, was warned of running low.
+    low_time_warned: [bool; 2],
+}
+
+impl Clock {
+    pub fn new(base: Duration, increment: Duration) -> Self {
+        Clock {
+            white_time: base,
+            black_time: base,
+            increment,
+            last_tick: None,
+            low_time_warned: [false; 2],
+        }
+    }
+
+    pub fn remaining(&self, color: Color) -> Duration {
+        match color {
+            Color::White => self.white_time,
+            Color::Black => self.black_time,
+        }
+    }
+
+    fn remaining_mut(&mut self, color: Color) -> &mut Duration {
+        match color {
+            Color::White => &mut self.white_time,
+            Color::Black => &mut self.black_time,
+        }
+    }
+
+    /// Charges the time since the previous tick to `to_move`, returning
+    /// whether their flag fell.
+    pub fn tick(&mut self, now: Instant, to_move: Color) -> bool {
+        if let Some(last_tick) = self.last_tick {
+            let remaining = self.remaining_mut(to_move);
+            *remaining = remaining.saturating_sub(now.duration_since(last_tick));
+        }
+        self.last_tick = Some(now);
+        self.remaining(to_move).is_zero()
+    }
+
+    /// Whether `color` just dropped below [`LOW_TIME`]. Only reported once
+    /// until their time goes back above it, e.g. thanks to the increment.
+    pub fn crossed_low_time(&mut self, color: Color) -> bool {
+        let low = self.remaining(color) < LOW_TIME;
+        let warned = &mut self.low_time_warned[color as usize];
+        let crossed = low && !*warned;
+        *warned = low;
+        crossed
+    }
+
+    /// Stops counting until the next tick, e.g. while the game is over.
+    pub fn pause(&mut self) {
+        self.last_tick = None;
+    }
+
+    /// Ends the turn of `mover`, adding the increment. Returns the time they
+    /// have left.
+    pub fn press(&mut self, mover: Color) -> Duration {
+        if self.last_tick.is_some() {
+            self.tick(Instant::now(), mover);
+        }
+        let increment = self.increment;
+        let remaining = self.remaining_mut(mover);
+        *remaining += increment;
+        *remaining
+    }
+}
+
+/// Clock reading such as `4:59`, or `1:02:03` past an hour.
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
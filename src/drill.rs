@@ -0,0 +1,68 @@
+use cozy_chess::{Color, Move};
+
+use crate::puzzle::Attempt;
+
+/// An opening line practised from one side, with the other side's book
+/// moves played automatically. The line starts from the initial position.
+#[derive(Debug)]
+pub struct Drill {
+    pub line: Vec<Move>,
+    pub side: Color,
+    /// Number of moves of the line played in the current run.
+    pub progress: usize,
+    /// Furthest any run got into the line.
+    pub best: usize,
+    /// The line move the user failed to find, ending the run.
+    pub expected: Option<Move>,
+}
+
+impl Drill {
+    pub fn new(line: Vec<Move>, side: Color) -> Self {
+        Drill {
+            line,
+            side,
+            progress: 0,
+            best: 0,
+            expected: None,
+        }
+    }
+
+    /// Starts a new run, returning White's first move when practising Black.
+    pub fn restart(&mut self) -> Option<Move> {
+        self.progress = 0;
+        self.expected = None;
+
+        if self.side == Color::Black {
+            let first = self.line.first().copied()?;
+            self.progress = 1;
+            return Some(first);
+        }
+        None
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.progress >= self.line.len()
+    }
+
+    /// Checks the user's move `mv` against the line, giving the book reply
+    /// to play when the line goes on.
+    pub fn attempt(&mut self, mv: Move) -> Attempt {
+        let expected = self.line[self.progress];
+        if mv != expected {
+            self.expected = Some(expected);
+            return Attempt::Wrong;
+        }
+
+        self.progress += 1;
+        let reply = self.line.get(self.progress).copied();
+        if reply.is_some() {
+            self.progress += 1;
+        }
+        self.best = self.best.max(self.progress);
+
+        match reply {
+            Some(reply) => Attempt::Correct(reply),
+            None => Attempt::Solved,
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cozy_chess::{Board, Move};
+use iced::futures::channel::oneshot;
+
+use crate::position;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long an engine gets to answer `stop` or `quit` before it's given up on.
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionKind {
+    Spin { min: i32, max: i32 },
+    Check,
+    Combo { vars: Vec<String> },
+    Button,
+    String,
+}
+
+/// An `option` advertised by the engine, along with its current value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UciOption {
+    pub name: String,
+    pub kind: OptionKind,
+    pub value: String,
+}
+
+impl UciOption {
+    /// Parses a line like `option name Hash type spin default 16 min 1 max 1024`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix("option name ")?;
+        let (name, rest) = rest.split_once(" type ")?;
+
+        let mut tokens = rest.split_whitespace();
+        let kind_name = tokens.next()?;
+
+        // values may contain spaces, so collect everything up to the next keyword
+        let mut default = String::new();
+        let mut min = 0;
+        let mut max = 0;
+        let mut vars = Vec::new();
+        let mut current: Option<&str> = None;
+        let mut value = String::new();
+
+        let mut finish = |keyword: Option<&str>, value: &mut String| {
+            match keyword {
+                Some("default") => default = value.clone(),
+                Some("min") => min = value.parse().unwrap_or(i32::MIN),
+                Some("max") => max = value.parse().unwrap_or(i32::MAX),
+                Some("var") => vars.push(value.clone()),
+                _ => {}
+            }
+            value.clear();
+        };
+
+        for token in tokens {
+            if matches!(token, "default" | "min" | "max" | "var") {
+                finish(current, &mut value);
+                current = Some(token);
+            } else {
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+                value += token;
+            }
+        }
+        finish(current, &mut value);
+
+        let kind = match kind_name {
+            "spin" => OptionKind::Spin { min, max },
+            "check" => OptionKind::Check,
+            "combo" => OptionKind::Combo { vars },
+            "button" => OptionKind::Button,
+            "string" => OptionKind::String,
+            _ => return None,
+        };
+
+        Some(UciOption {
+            name: name.trim().to_owned(),
+            kind,
+            value: default,
+        })
+    }
+}
+
+/// An engine started in the background, on its way to the app. Messages are
+/// cloned, so the engine is taken out exactly once.
+#[derive(Clone)]
+pub struct StartedEngine(Arc<Mutex<Option<Engine>>>);
+
+impl StartedEngine {
+    pub fn take(&self) -> Option<Engine> {
+        self.0.lock().ok()?.take()
+    }
+}
+
+impl fmt::Debug for StartedEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StartedEngine")
+    }
+}
+
+/// Runs [`Engine::start`] on its own thread so a slow handshake doesn't
+/// freeze the UI.
+pub async fn start_in_background(path: PathBuf) -> Result<StartedEngine, String> {
+    let (sender, receiver) = oneshot::channel();
+
+    thread::spawn(move || {
+        let _ = sender.send(Engine::start(&path));
+    });
+
+    match receiver.await {
+        Ok(Ok(engine)) => Ok(StartedEngine(Arc::new(Mutex::new(Some(engine))))),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(_) => Err("engine start was interrupted".to_owned()),
+    }
+}
+
+/// A running UCI engine process.
+pub struct Engine {
+    pub path: PathBuf,
+    pub name: String,
+    pub options: Vec<UciOption>,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    /// Position of the search in progress, used to read its `bestmove`.
+    searching: Option<Board>,
+}
+
+impl Engine {
+    /// Launches the engine and waits for it to finish the `uci` handshake.
+    pub fn start(path: &Path) -> io::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or_else(|| io::Error::other("engine has no stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| io::Error::other("engine has no stdout"))?;
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Engine {
+            path: path.to_path_buf(),
+            name: path.display().to_string(),
+            options: Vec::new(),
+            child,
+            stdin,
+            lines,
+            searching: None,
+        };
+
+        engine.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = engine.recv_until(deadline)?;
+            if line == "uciok" {
+                break;
+            } else if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.to_owned();
+            } else if let Some(option) = UciOption::parse(&line) {
+                engine.options.push(option);
+            }
+        }
+
+        Ok(engine)
+    }
+
+    pub fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{command}")?;
+        self.stdin.flush()
+    }
+
+    fn recv_until(&self, deadline: Instant) -> io::Result<String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line.trim().to_owned()),
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(io::ErrorKind::TimedOut, "engine stopped responding")),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "engine exited")),
+        }
+    }
+
+    /// Tells the engine the next position belongs to a different game.
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.stop()?;
+        self.send("ucinewgame")
+    }
+
+    /// Ends the search in progress, if any, waiting briefly for the
+    /// `bestmove` it owes so it can't be mistaken for the next answer.
+    pub fn stop(&mut self) -> io::Result<()> {
+        if self.searching.take().is_none() {
+            return Ok(());
+        }
+
+        self.send("stop")?;
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while !self.recv_until(deadline)?.starts_with("bestmove") {}
+        Ok(())
+    }
+
+    /// Asks the engine to quit, killing it if it hasn't exited in time.
+    /// Does nothing to an engine that already exited.
+    pub fn shutdown(&mut self) {
+        if matches!(self.child.try_wait(), Ok(Some(_))) {
+            return;
+        }
+
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            match self.child.try_wait() {
+                Ok(Some(_)) => return,
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(_) => break,
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.searching.is_some()
+    }
+
+    /// Starts searching the position reached by playing `moves` from `start`.
+    /// The answer is collected with [`Engine::poll_best_move`].
+    pub fn go(&mut self, start: &Board, moves: &[Move], movetime: Duration) -> io::Result<()> {
+        let mut board = start.clone();
+        let mut command = format!("position fen {start}");
+        if !moves.is_empty() {
+            command += " moves";
+        }
+        for &mv in moves {
+            command += &format!(" {}", position::to_standard_castling(&board, mv));
+            board.play_unchecked(mv);
+        }
+
+        self.send(&command)?;
+        self.send(&format!("go movetime {}", movetime.as_millis()))?;
+        self.searching = Some(board);
+        Ok(())
+    }
+
+    /// The engine's move once the current search has finished, without blocking.
+    pub fn poll_best_move(&mut self) -> Option<io::Result<Move>> {
+        loop {
+            let line = match self.lines.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    self.searching = None;
+                    return Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "engine exited")));
+                },
+            };
+
+            let Some(rest) = line.trim().strip_prefix("bestmove") else {
+                continue;
+            };
+            let Some(board) = self.searching.take() else {
+                continue;
+            };
+
+            let uci = rest.split_whitespace().next().unwrap_or("");
+            return Some(
+                position::parse_uci_move(&board, uci)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("illegal bestmove {uci}"))),
+            );
+        }
+    }
+
+    /// Whether `name` is a button, an action rather than a value to keep.
+    pub fn is_button(&self, name: &str) -> bool {
+        self.options.iter().any(|option| option.name == name && option.kind == OptionKind::Button)
+    }
+
+    /// Sends `setoption`, remembering the value. Buttons take no value.
+    pub fn set_option(&mut self, name: &str, value: &str) -> io::Result<()> {
+        let Some(option) = self.options.iter_mut().find(|option| option.name == name) else {
+            return Ok(());
+        };
+
+        if option.kind == OptionKind::Button {
+            return self.send(&format!("setoption name {name}"));
+        }
+
+        option.value = value.to_owned();
+        self.send(&format!("setoption name {name} value {value}"))
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use crate::engine::Engine;
+
+/// Automated games between the main engine and an opponent engine, with
+/// colors alternating every game.
+pub struct EngineMatch {
+    pub opponent: Engine,
+    pub games: u32,
+    pub played: u32,
+    pub movetime: Duration,
+    /// Whether the main engine has White in the current game.
+    pub main_is_white: bool,
+    /// Score from the main engine's point of view.
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl EngineMatch {
+    pub fn new(opponent: Engine, games: u32, movetime: Duration) -> Self {
+        EngineMatch {
+            opponent,
+            games,
+            played: 0,
+            movetime,
+            main_is_white: true,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+        }
+    }
+
+    /// Counts a finished game and switches colors for the next one.
+    pub fn record(&mut self, result: &str) {
+        match (result, self.main_is_white) {
+            ("1-0", true) | ("0-1", false) => self.wins += 1,
+            ("1-0", false) | ("0-1", true) => self.losses += 1,
+            _ => self.draws += 1,
+        }
+        self.played += 1;
+        self.main_is_white = !self.main_is_white;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.played >= self.games
+    }
+
+    pub fn score(&self) -> String {
+        format!("{}-{}-{}", self.wins, self.draws, self.losses)
+    }
+}
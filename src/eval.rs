@@ -0,0 +1,102 @@
+use std::fmt;
+
+use cozy_chess::{Board, Color, GameStatus, Piece};
+
+/// An evaluation, always from White's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// The given side mates in this many moves.
+    Mate(Color, u32),
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Score::Centipawns(cp) => write!(f, "{:+.2}", cp as f32 / 100.0),
+            Score::Mate(Color::White, moves) => write!(f, "M{moves}"),
+            Score::Mate(Color::Black, moves) => write!(f, "-M{moves}"),
+        }
+    }
+}
+
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 100,
+        Piece::Knight => 320,
+        Piece::Bishop => 330,
+        Piece::Rook => 500,
+        Piece::Queen => 900,
+        Piece::King => 0,
+    }
+}
+
+/// Difference in piece counts between the sides, per piece type, from
+/// White's point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Imbalance {
+    /// White's count minus Black's, for each piece but the king.
+    pub counts: [i32; 5],
+}
+
+impl Imbalance {
+    const PIECES: [Piece; 5] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight, Piece::Pawn];
+
+    pub fn of(board: &Board) -> Self {
+        Imbalance {
+            counts: Self::PIECES.map(|piece| {
+                board.colored_pieces(Color::White, piece).len() as i32 - board.colored_pieces(Color::Black, piece).len() as i32
+            }),
+        }
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.counts.iter().all(|&count| count == 0)
+    }
+}
+
+/// As in `White: +1 knight, -1 pawn`.
+impl fmt::Display for Imbalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = Self::PIECES.into_iter().zip(self.counts)
+            .filter(|&(_, count)| count != 0)
+            .map(|(piece, count)| {
+                let name = format!("{piece:?}").to_lowercase();
+                let plural = if count.abs() > 1 { "s" } else { "" };
+                format!("{count:+} {name}{plural}")
+            })
+            .collect();
+        write!(f, "White: {}", parts.join(", "))
+    }
+}
+
+pub fn evaluate(board: &Board) -> Score {
+    match board.status() {
+        GameStatus::Won => Score::Mate(!board.side_to_move(), 0),
+        GameStatus::Drawn => Score::Centipawns(0),
+        GameStatus::Ongoing => Score::Centipawns(static_eval(board)),
+    }
+}
+
+/// Material balance, plus a small bonus for mobility of the side to move.
+/// From White's point of view, and blind to checkmate and draws.
+pub fn static_eval(board: &Board) -> i32 {
+    let mut score = 0;
+    for piece in Piece::ALL {
+        let white = board.colored_pieces(Color::White, piece).len() as i32;
+        let black = board.colored_pieces(Color::Black, piece).len() as i32;
+        score += (white - black) * piece_value(piece);
+    }
+
+    let mut mobility = 0;
+    board.generate_moves(|moves| {
+        mobility += moves.len() as i32;
+        false
+    });
+    score += match board.side_to_move() {
+        Color::White => mobility,
+        Color::Black => -mobility,
+    };
+
+    score
+}
@@ -0,0 +1,113 @@
+use std::thread;
+
+use cozy_chess::{Board, Color, Move};
+use iced::futures::channel::oneshot;
+use iced::widget::canvas::{self, Event, Frame, Geometry, Path, Stroke};
+use iced::{Point, Rectangle, Renderer, Theme, mouse};
+
+use crate::Message;
+use crate::eval::{self, Score};
+
+/// Evaluations beyond this many centipawns are drawn at the edge of the graph.
+pub const CLAMP: i32 = 1000;
+
+/// Clamped centipawns from White's point of view, with mates at the edges.
+pub fn graph_value(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(cp) => cp.clamp(-CLAMP, CLAMP),
+        Score::Mate(Color::White, _) => CLAMP,
+        Score::Mate(Color::Black, _) => -CLAMP,
+    }
+}
+
+/// Graph values of every position of the game, the start position included.
+pub fn evaluate_game(start: &Board, moves: &[Move]) -> Vec<i32> {
+    let mut board = start.clone();
+    let mut values = vec![graph_value(eval::evaluate(&board))];
+    for &mv in moves {
+        board.play_unchecked(mv);
+        values.push(graph_value(eval::evaluate(&board)));
+    }
+    values
+}
+
+/// Runs [`evaluate_game`] on its own thread so the UI stays responsive.
+pub async fn evaluate_in_background(start: Board, moves: Vec<Move>) -> Vec<i32> {
+    let (sender, receiver) = oneshot::channel();
+
+    thread::spawn(move || {
+        let _ = sender.send(evaluate_game(&start, &moves));
+    });
+
+    receiver.await.unwrap_or_default()
+}
+
+/// Line chart of the evaluation by ply. Clicking it jumps to that ply.
+pub struct EvalGraph<'a> {
+    pub values: &'a [i32],
+    /// Ply shown on the board.
+    pub current: usize,
+}
+
+impl EvalGraph<'_> {
+    fn point(&self, ply: usize, size: iced::Size) -> Point {
+        let last = self.values.len().saturating_sub(1).max(1);
+        let value = self.values.get(ply).copied().unwrap_or(0);
+        Point::new(
+            ply as f32 / last as f32 * size.width,
+            size.height / 2.0 - value as f32 / CLAMP as f32 * size.height / 2.0,
+        )
+    }
+}
+
+impl canvas::Program<Message> for EvalGraph<'_> {
+    type State = ();
+
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Option<canvas::Action<Message>> {
+        let position = cursor.position_in(bounds)?;
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let last = self.values.len().saturating_sub(1);
+                let ply = (position.x / bounds.width * last as f32).round() as usize;
+                Some(canvas::Action::publish(Message::JumpToPly(ply.min(last))).and_capture())
+            },
+            _ => None,
+        }
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let size = bounds.size();
+        let mut frame = Frame::new(renderer, size);
+        frame.fill_rectangle(Point::ORIGIN, size, iced::Color::from_rgb(0.15, 0.15, 0.15));
+
+        let zero = Path::line(Point::new(0.0, size.height / 2.0), Point::new(size.width, size.height / 2.0));
+        frame.stroke(&zero, Stroke::default().with_color(iced::Color::from_rgb(0.4, 0.4, 0.4)));
+
+        let line = Path::new(|builder| {
+            builder.move_to(self.point(0, size));
+            for ply in 1..self.values.len() {
+                builder.line_to(self.point(ply, size));
+            }
+        });
+        frame.stroke(&line, Stroke::default().with_color(iced::Color::WHITE).with_width(2.0));
+
+        let marker = Path::circle(self.point(self.current, size), 4.0);
+        frame.fill(&marker, iced::Color::from_rgb(1.0, 0.6, 0.0));
+
+        vec![frame.into_geometry()]
+    }
+}
@@ -0,0 +1,340 @@
+use cozy_chess::{
+    BitBoard, Board, Color, File, Piece, Rank, Square,
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves,
+};
+
+/// `board` as FEN, in Shredder notation when asked to or when standard FEN
+/// can't describe its castling rights, so the output always loads again.
+pub fn write_fen(board: &Board, shredder: bool) -> String {
+    if shredder || needs_shredder_fen(board) {
+        format!("{board:#}")
+    } else {
+        board.to_string()
+    }
+}
+
+/// Castling rights that `KQkq` can't express, as in most Chess960 starting
+/// positions: a king off the e-file or a castling rook outside its corner.
+pub fn needs_shredder_fen(board: &Board) -> bool {
+    Color::ALL.into_iter().any(|color| {
+        let rights = board.castle_rights(color);
+        let off_corner = rights.short.is_some_and(|file| file != File::H)
+            || rights.long.is_some_and(|file| file != File::A);
+        let can_castle = rights.short.is_some() || rights.long.is_some();
+        off_corner || (can_castle && board.king(color).file() != File::E)
+    })
+}
+
+type Placement = [Option<(Color, Piece)>; Square::NUM];
+
+const FIELD_NAMES: [&str; 6] = ["placement", "side to move", "castling", "en passant", "halfmove clock", "fullmove number"];
+
+/// A FEN field that differs between two positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+/// The fields of `after` that differ from `before`, found by splitting both
+/// FENs into their six fields. A missing field reads as empty.
+pub fn diff(before: &str, after: &str) -> Vec<FieldChange> {
+    let before: Vec<&str> = before.split_whitespace().collect();
+    let after: Vec<&str> = after.split_whitespace().collect();
+
+    FIELD_NAMES.into_iter().enumerate()
+        .filter_map(|(index, field)| {
+            let before = before.get(index).copied().unwrap_or_default();
+            let after = after.get(index).copied().unwrap_or_default();
+            (before != after).then(|| FieldChange { field, before: before.to_owned(), after: after.to_owned() })
+        })
+        .collect()
+}
+
+/// Reads a FEN, standard or Shredder. When the position can't be loaded,
+/// every problem found with it is returned instead, e.g. `two white kings`.
+pub fn parse_fen(fen: &str) -> Result<Board, Vec<String>> {
+    let fen = fen.trim();
+    let error = match Board::from_fen(fen, false).or_else(|_| Board::from_fen(fen, true)) {
+        Ok(board) => return Ok(board),
+        Err(error) => error,
+    };
+
+    let mut problems = diagnose(fen);
+    // cozy_chess may still object to something the checks don't look at
+    if problems.is_empty() {
+        problems.push(format!("invalid position ({error:?})"));
+    }
+    Err(problems)
+}
+
+fn diagnose(fen: &str) -> Vec<String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let mut problems = Vec::new();
+    if !(4..=6).contains(&fields.len()) {
+        problems.push(format!("expected 6 fields, found {}", fields.len()));
+    }
+
+    let placement = match fields.first().map(|placement| read_placement(placement)) {
+        Some(Ok(placement)) => placement,
+        Some(Err(problem)) => {
+            problems.push(problem);
+            return problems;
+        },
+        None => return problems,
+    };
+
+    let side_to_move = match fields.get(1).copied() {
+        Some("w") => Some(Color::White),
+        Some("b") => Some(Color::Black),
+        other => {
+            problems.push(format!("side to move must be w or b, not {}", other.unwrap_or("missing")));
+            None
+        },
+    };
+
+    for color in Color::ALL {
+        let name = color_name(color);
+        let count = |piece: Piece| placement.iter().filter(|&&square| square == Some((color, piece))).count();
+
+        match count(Piece::King) {
+            0 => problems.push(format!("no {name} king")),
+            1 => {},
+            2 => problems.push(format!("two {name} kings")),
+            kings => problems.push(format!("{kings} {name} kings")),
+        }
+        if count(Piece::Pawn) > 8 {
+            problems.push(format!("too many {name} pawns ({}, at most 8)", count(Piece::Pawn)));
+        }
+        let total: usize = Piece::ALL.into_iter().map(count).sum();
+        if total > 16 {
+            problems.push(format!("too many {name} pieces ({total}, at most 16)"));
+        }
+    }
+
+    for square in Square::ALL {
+        let back_rank = matches!(square.rank(), Rank::First | Rank::Eighth);
+        if back_rank && placement[square as usize].is_some_and(|(_, piece)| piece == Piece::Pawn) {
+            problems.push(format!("pawn on {square}, pawns can't stand on the first or eighth rank"));
+        }
+    }
+
+    if let Some(side_to_move) = side_to_move {
+        let waiting = !side_to_move;
+        if let [king] = squares_of(&placement, waiting, Piece::King)[..] {
+            if is_attacked(&placement, king, side_to_move) {
+                problems.push(format!(
+                    "{} is in check with {} to move",
+                    color_name(waiting),
+                    color_name(side_to_move),
+                ));
+            }
+        }
+    }
+
+    if let Some(&rights) = fields.get(2).filter(|&&rights| rights != "-") {
+        for right in rights.chars() {
+            if let Some(problem) = castling_problem(&placement, right) {
+                problems.push(problem);
+            }
+        }
+    }
+
+    if let (Some(&en_passant), Some(side_to_move)) = (fields.get(3).filter(|&&square| square != "-"), side_to_move) {
+        problems.extend(en_passant_problem(&placement, en_passant, side_to_move));
+    }
+
+    for (field, name) in [(4, "halfmove clock"), (5, "fullmove number")] {
+        if fields.get(field).is_some_and(|value| value.parse::<u16>().is_err()) {
+            problems.push(format!("{name} must be a number"));
+        }
+    }
+
+    problems
+}
+
+/// The pieces of the first FEN field, by square.
+fn read_placement(placement: &str) -> Result<Placement, String> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(format!("board has {} ranks instead of 8", ranks.len()));
+    }
+
+    let mut squares = [None; Square::NUM];
+    for (row, text) in ranks.into_iter().enumerate() {
+        let rank = Rank::index(7 - row);
+        let mut file = 0;
+        for c in text.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                file += empty as usize;
+                continue;
+            }
+
+            let piece = match c.to_ascii_lowercase() {
+                'p' => Piece::Pawn,
+                'n' => Piece::Knight,
+                'b' => Piece::Bishop,
+                'r' => Piece::Rook,
+                'q' => Piece::Queen,
+                'k' => Piece::King,
+                _ => return Err(format!("unknown piece '{c}' on rank {rank}")),
+            };
+            let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+            if file < 8 {
+                squares[Square::new(File::index(file), rank) as usize] = Some((color, piece));
+            }
+            file += 1;
+        }
+
+        if file != 8 {
+            return Err(format!("rank {rank} has {file} squares instead of 8"));
+        }
+    }
+
+    Ok(squares)
+}
+
+fn squares_of(placement: &Placement, color: Color, piece: Piece) -> Vec<Square> {
+    Square::ALL.into_iter().filter(|&square| placement[square as usize] == Some((color, piece))).collect()
+}
+
+fn bitboard_of(squares: impl IntoIterator<Item = Square>) -> BitBoard {
+    squares.into_iter().fold(BitBoard::EMPTY, |bitboard, square| bitboard | square.bitboard())
+}
+
+fn is_attacked(placement: &Placement, square: Square, by: Color) -> bool {
+    let occupied = bitboard_of(Square::ALL.into_iter().filter(|&square| placement[square as usize].is_some()));
+    let pieces = |piece: Piece| bitboard_of(squares_of(placement, by, piece));
+    let queens = pieces(Piece::Queen);
+
+    let attackers = (get_pawn_attacks(square, !by) & pieces(Piece::Pawn))
+        | (get_knight_moves(square) & pieces(Piece::Knight))
+        | (get_king_moves(square) & pieces(Piece::King))
+        | (get_bishop_moves(square, occupied) & (pieces(Piece::Bishop) | queens))
+        | (get_rook_moves(square, occupied) & (pieces(Piece::Rook) | queens));
+    !attackers.is_empty()
+}
+
+/// Checks one castling right, `KQkq` or a Shredder file letter, against the
+/// king and rook it needs.
+fn castling_problem(placement: &Placement, right: char) -> Option<String> {
+    let color = if right.is_ascii_uppercase() { Color::White } else { Color::Black };
+    let name = color_name(color);
+    let back_rank = Rank::First.relative_to(color);
+
+    let Some(king) = squares_of(placement, color, Piece::King).into_iter().find(|king| king.rank() == back_rank) else {
+        return Some(format!("castling right {right} without the {name} king on its first rank"));
+    };
+    let rook_on = |file: File| placement[Square::new(file, back_rank) as usize] == Some((color, Piece::Rook));
+
+    // KQkq name the outermost rook, so the usual corner is only what's reported
+    let (found, expected) = match right.to_ascii_lowercase() {
+        'k' => (File::ALL.into_iter().any(|file| file as usize > king.file() as usize && rook_on(file)), File::H),
+        'q' => (File::ALL.into_iter().any(|file| (file as usize) < king.file() as usize && rook_on(file)), File::A),
+        'a'..='h' => {
+            let file = File::index((right.to_ascii_lowercase() as u8 - b'a') as usize);
+            (rook_on(file), file)
+        },
+        _ => return Some(format!("unknown castling right {right}")),
+    };
+
+    (!found).then(|| format!("castling right {right} without a {name} rook on {}", Square::new(expected, back_rank)))
+}
+
+fn en_passant_problem(placement: &Placement, en_passant: &str, side_to_move: Color) -> Option<String> {
+    let Ok(square) = en_passant.parse::<Square>() else {
+        return Some(format!("en passant square {en_passant} isn't a square"));
+    };
+
+    // the pawn that just moved two squares stands right past the en passant square
+    let mover = !side_to_move;
+    let pawn = Square::new(square.file(), Rank::Fourth.relative_to(mover));
+    let valid = square.rank() == Rank::Third.relative_to(mover)
+        && placement[pawn as usize] == Some((mover, Piece::Pawn));
+    (!valid).then(|| format!("en passant square {square} isn't behind a {} pawn that just moved two squares", color_name(mover)))
+}
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_problem(fen: &str, problem: &str) {
+        let problems = diagnose(fen);
+        assert!(problems.contains(&problem.to_owned()), "{fen}: expected {problem:?}, got {problems:?}");
+    }
+
+    #[test]
+    fn reports_missing_and_extra_kings() {
+        assert_problem("8/8/8/8/8/8/8/4K3 w - - 0 1", "no black king");
+        assert_problem("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", "two white kings");
+        assert_eq!(parse_fen("4k3/8/8/8/8/8/8/3KK3 w - - 0 1").err(), Some(vec!["two white kings".to_owned()]));
+    }
+
+    #[test]
+    fn reports_too_much_material() {
+        assert_problem("4k3/8/8/8/8/PPPPPPPP/P7/4K3 w - - 0 1", "too many white pawns (9, at most 8)");
+        assert_problem("4k3/8/8/8/NNNNNNNN/NNNNNNNN/8/4K3 w - - 0 1", "too many white pieces (17, at most 16)");
+    }
+
+    #[test]
+    fn reports_pawns_on_the_back_rank() {
+        assert_problem("4k2P/8/8/8/8/8/8/4K3 w - - 0 1", "pawn on h8, pawns can't stand on the first or eighth rank");
+    }
+
+    #[test]
+    fn reports_the_waiting_side_in_check() {
+        assert_problem("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1", "black is in check with white to move");
+    }
+
+    #[test]
+    fn reports_castling_rights_without_their_rook() {
+        assert_problem("4k3/8/8/8/8/8/8/4K3 w K - 0 1", "castling right K without a white rook on h1");
+    }
+
+    #[test]
+    fn reports_an_en_passant_square_without_its_pawn() {
+        assert_problem(
+            "4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+            "en passant square e6 isn't behind a black pawn that just moved two squares",
+        );
+    }
+
+    #[test]
+    fn reports_bad_clocks() {
+        assert_problem("4k3/8/8/8/8/8/8/4K3 w - - x 1", "halfmove clock must be a number");
+        assert_problem("4k3/8/8/8/8/8/8/4K3 w - - 0 y", "fullmove number must be a number");
+    }
+
+    #[test]
+    fn round_trips_chess960_castling_rights() {
+        let fen = "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1";
+        let board = Board::from_fen(fen, true).unwrap();
+        assert!(needs_shredder_fen(&board));
+        assert_eq!(write_fen(&board, false), write_fen(&board, true));
+
+        for shredder in [false, true] {
+            let written = write_fen(&board, shredder);
+            assert!(written.contains(" HFhf "), "{written}");
+            let read = parse_fen(&written).unwrap();
+            assert!(read.same_position(&board));
+            assert_eq!(write_fen(&read, shredder), written);
+        }
+    }
+
+    #[test]
+    fn writes_standard_castling_rights_unless_asked() {
+        let board = Board::default();
+        assert!(!needs_shredder_fen(&board));
+        assert_eq!(write_fen(&board, false), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!(write_fen(&board, true).contains(" HAha "));
+        assert!(parse_fen(&write_fen(&board, true)).unwrap().same_position(&board));
+    }
+}
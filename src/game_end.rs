@@ -0,0 +1,180 @@
+use cozy_chess::{Board, Color, GameStatus};
+
+use crate::position;
+
+/// Why a game is over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEnd {
+    /// The given side delivered mate.
+    Checkmate(Color),
+    Stalemate,
+    Repetition,
+    FiftyMove,
+    InsufficientMaterial,
+    Agreement,
+    /// The given side ran out of time.
+    Timeout(Color),
+    /// The given side resigned.
+    Resignation(Color),
+    /// An imported game with this result, for which the reason isn't known.
+    Recorded(String),
+}
+
+impl GameEnd {
+    /// How the game on `board` ended by the rules, or `None` while it goes on.
+    /// `hashes` are the positions so far, the current one last.
+    pub fn detect(board: &Board, hashes: &[u64]) -> Option<Self> {
+        match board.status() {
+            GameStatus::Won => Some(GameEnd::Checkmate(!board.side_to_move())),
+            GameStatus::Drawn if position::count_legal_moves(board) == 0 => Some(GameEnd::Stalemate),
+            GameStatus::Drawn => Some(GameEnd::FiftyMove),
+            GameStatus::Ongoing => {
+                let repetitions = hashes.iter().filter(|&&hash| hash == board.hash()).count();
+                if repetitions >= 3 {
+                    Some(GameEnd::Repetition)
+                } else if position::insufficient_material(board) {
+                    Some(GameEnd::InsufficientMaterial)
+                } else {
+                    None
+                }
+            },
+        }
+    }
+
+    /// Value of the PGN `Result` tag.
+    pub fn result(&self) -> &str {
+        match self {
+            GameEnd::Checkmate(Color::White)
+            | GameEnd::Timeout(Color::Black)
+            | GameEnd::Resignation(Color::Black) => "1-0",
+            GameEnd::Checkmate(Color::Black)
+            | GameEnd::Timeout(Color::White)
+            | GameEnd::Resignation(Color::White) => "0-1",
+            GameEnd::Stalemate
+            | GameEnd::Repetition
+            | GameEnd::FiftyMove
+            | GameEnd::InsufficientMaterial
+            | GameEnd::Agreement => "1/2-1/2",
+            GameEnd::Recorded(result) => result,
+        }
+    }
+
+    /// Value of the PGN `Termination` tag, when known.
+    pub fn termination(&self) -> Option<&'static str> {
+        match self {
+            GameEnd::Timeout(_) => Some("time forfeit"),
+            GameEnd::Recorded(_) => None,
+            _ => Some("normal"),
+        }
+    }
+
+    /// Sentence shown when the game ends, e.g. `White wins by checkmate`.
+    pub fn description(&self) -> String {
+        match self {
+            GameEnd::Checkmate(winner) => format!("{winner:?} wins by checkmate"),
+            GameEnd::Stalemate => "Draw by stalemate".to_owned(),
+            GameEnd::Repetition => "Draw by threefold repetition".to_owned(),
+            GameEnd::FiftyMove => "Draw by the fifty-move rule".to_owned(),
+            GameEnd::InsufficientMaterial => "Draw by insufficient material".to_owned(),
+            GameEnd::Agreement => "Draw agreed".to_owned(),
+            GameEnd::Timeout(loser) => format!("{loser:?} lost on time"),
+            GameEnd::Resignation(loser) => format!("{loser:?} resigns"),
+            GameEnd::Recorded(_) => "Game over".to_owned(),
+        }
+    }
+
+    /// Compact form used to store the reason, e.g. `timeout white`.
+    pub fn to_key(&self) -> String {
+        match self {
+            GameEnd::Checkmate(color) => format!("checkmate {}", color_key(*color)),
+            GameEnd::Stalemate => "stalemate".to_owned(),
+            GameEnd::Repetition => "repetition".to_owned(),
+            GameEnd::FiftyMove => "fifty_move".to_owned(),
+            GameEnd::InsufficientMaterial => "insufficient_material".to_owned(),
+            GameEnd::Agreement => "agreement".to_owned(),
+            GameEnd::Timeout(color) => format!("timeout {}", color_key(*color)),
+            GameEnd::Resignation(color) => format!("resignation {}", color_key(*color)),
+            GameEnd::Recorded(result) => format!("recorded {result}"),
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        let (kind, argument) = key.split_once(' ').unwrap_or((key, ""));
+        let color = || match argument {
+            "white" => Some(Color::White),
+            "black" => Some(Color::Black),
+            _ => None,
+        };
+
+        match kind {
+            "checkmate" => color().map(GameEnd::Checkmate),
+            "stalemate" => Some(GameEnd::Stalemate),
+            "repetition" => Some(GameEnd::Repetition),
+            "fifty_move" => Some(GameEnd::FiftyMove),
+            "insufficient_material" => Some(GameEnd::InsufficientMaterial),
+            "agreement" => Some(GameEnd::Agreement),
+            "timeout" => color().map(GameEnd::Timeout),
+            "resignation" => color().map(GameEnd::Resignation),
+            "recorded" if !argument.is_empty() => Some(GameEnd::Recorded(argument.to_owned())),
+            _ => None,
+        }
+    }
+}
+
+fn color_key(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn every_end() -> Vec<GameEnd> {
+        let mut ends = vec![
+            GameEnd::Stalemate,
+            GameEnd::Repetition,
+            GameEnd::FiftyMove,
+            GameEnd::InsufficientMaterial,
+            GameEnd::Agreement,
+            GameEnd::Recorded("1-0".to_owned()),
+        ];
+        for color in Color::ALL {
+            ends.extend([GameEnd::Checkmate(color), GameEnd::Timeout(color), GameEnd::Resignation(color)]);
+        }
+        ends
+    }
+
+    #[test]
+    fn result_matches_each_end() {
+        let table = [
+            (GameEnd::Checkmate(Color::White), "1-0"),
+            (GameEnd::Checkmate(Color::Black), "0-1"),
+            (GameEnd::Timeout(Color::White), "0-1"),
+            (GameEnd::Timeout(Color::Black), "1-0"),
+            (GameEnd::Resignation(Color::White), "0-1"),
+            (GameEnd::Resignation(Color::Black), "1-0"),
+            (GameEnd::Stalemate, "1/2-1/2"),
+            (GameEnd::Repetition, "1/2-1/2"),
+            (GameEnd::FiftyMove, "1/2-1/2"),
+            (GameEnd::InsufficientMaterial, "1/2-1/2"),
+            (GameEnd::Agreement, "1/2-1/2"),
+            (GameEnd::Recorded("1-0".to_owned()), "1-0"),
+            (GameEnd::Recorded("*".to_owned()), "*"),
+        ];
+        for (end, result) in table {
+            assert_eq!(end.result(), result, "{end:?}");
+        }
+    }
+
+    #[test]
+    fn keys_read_back() {
+        for end in every_end() {
+            assert_eq!(GameEnd::from_key(&end.to_key()), Some(end.clone()), "{}", end.to_key());
+        }
+        assert_eq!(GameEnd::from_key("checkmate purple"), None);
+        assert_eq!(GameEnd::from_key("recorded"), None);
+    }
+}
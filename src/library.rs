@@ -0,0 +1,36 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use cozy_chess::{Board, Move};
+
+use crate::pgn;
+
+/// Identifies a game by its start position and moves, ignoring headers.
+pub fn game_hash(start: &Board, moves: &[Move]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    start.to_string().hash(&mut hasher);
+    for mv in moves {
+        mv.to_string().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// A PGN file in `dir`, other than `except`, that already holds this game.
+/// Files that can't be read or parsed are skipped.
+pub fn find_duplicate(dir: &Path, except: &Path, start: &Board, moves: &[Move]) -> Option<PathBuf> {
+    let wanted = game_hash(start, moves);
+
+    fs::read_dir(dir).ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path != except && path.extension().is_some_and(|extension| extension == "pgn"))
+        .find(|path| {
+            let Ok(contents) = fs::read_to_string(path) else {
+                return false;
+            };
+            pgn::split_games(&contents).into_iter()
+                .filter_map(|game| pgn::read_game(game).ok())
+                .any(|game| game_hash(&game.start, &game.moves) == wanted)
+        })
+}
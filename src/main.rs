@@ -1,182 +1,3917 @@
 use core::panic;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-use cozy_chess::{Board, Move, Piece, Rank, Square};
-use iced::widget::canvas::{self, Cache, Canvas, Geometry, Image, Event};
-use iced::widget::{container, image, row, text};
-use iced::{Element, Fill, Point, Rectangle, Renderer, Theme, mouse, Color, Size};
+mod annotations;
+mod bench;
+mod bitboard_view;
+mod book;
+mod bot;
+mod clock;
+mod drill;
+mod engine;
+mod engine_match;
+mod eval;
+mod eval_graph;
+mod fen;
+mod game_end;
+mod library;
+mod pgn;
+mod position;
+mod puzzle;
+mod render;
+mod review;
+mod san;
+mod search;
+mod session;
+mod settings;
+mod sound;
+
+use cozy_chess::{BitBoard, Board, GameStatus, Move, Piece, Rank, Square};
+use iced::widget::canvas::{self, Cache, Canvas, Event, Frame, Geometry, Image, Path, Stroke};
+use iced::widget::{button, center, checkbox, column, container, image, mouse_area, opaque, pick_list, radio, row, slider, stack, text, text_input};
+use iced::{Element, Fill, Font, Length, Point, Rectangle, Renderer, Subscription, Task, Theme, Vector, clipboard, keyboard, mouse, time, window, Color, Size};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use annotations::{Annotations, MarkColor};
+use bitboard_view::BitboardView;
+use bot::BotConfig;
+use clock::Clock;
+use drill::Drill;
+use engine::{Engine, OptionKind, StartedEngine};
+use engine_match::EngineMatch;
+use eval::{Imbalance, Score};
+use eval_graph::EvalGraph;
+use game_end::GameEnd;
+use position::Mobility;
+use puzzle::{Attempt, Puzzle, PuzzleSession};
+use render::{BoardColors, BoardRenderer};
+use review::{Judgement, MoveReview};
+use search::{OnlyMove, SearchResult};
+use session::Session;
+use settings::{GameMode, GameSetup, HighlightPreset, InputMode, LastMoveStyle, MoveSounds, Opponent, PieceFilter, PositionPreset, PromotionBehavior, Settings};
+use sound::{Audio, SoundKind};
+
+const RANDOM_POSITION_PLIES: usize = 40;
+
+/// Command line options.
+#[derive(Debug, Default)]
+struct Args {
+    /// Start from a random position generated with this seed.
+    random_position: Option<u64>,
+    /// Seed for the bot's random choices, to replay its games exactly.
+    bot_seed: Option<u64>,
+    /// UCI engine to launch at startup.
+    engine: Option<PathBuf>,
+    /// Directory holding the piece images.
+    assets: Option<PathBuf>,
+    /// Run the headless self-play benchmark instead of opening a window.
+    bench_selfplay: bool,
+    /// Games played by the benchmark.
+    games: Option<u32>,
+    /// Search depth used by the benchmark.
+    depth: Option<u32>,
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut args = Args::default();
+        let mut iter = std::env::args().skip(1).peekable();
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--random-position" => {
+                    let seed = iter.next_if(|value| !value.starts_with("--"))
+                        .and_then(|value| value.parse().ok())
+                        .unwrap_or_else(rand::random);
+                    args.random_position = Some(seed);
+                },
+                "--bot-seed" => args.bot_seed = iter.next().and_then(|value| value.parse().ok()),
+                "--engine" => args.engine = iter.next().map(PathBuf::from),
+                "--assets" => args.assets = iter.next().map(PathBuf::from),
+                "--bench-selfplay" => args.bench_selfplay = true,
+                "--games" => args.games = iter.next().and_then(|value| value.parse().ok()),
+                "--depth" => args.depth = iter.next().and_then(|value| value.parse().ok()),
+                _ => eprintln!("ignoring unknown argument {arg}"),
+            }
+        }
+
+        args
+    }
+}
 
 pub fn main() -> iced::Result {
+    let args = Args::parse();
+
+    if args.bench_selfplay {
+        bench::run_selfplay(args.games.unwrap_or(BENCH_GAMES), args.depth.unwrap_or(BENCH_DEPTH));
+        return Ok(());
+    }
+
     iced::application("Chess", VisualBoard::update, VisualBoard::view)
-        .window_size(Size {
-            width: 1280.0,
-            height: 720.0,
+        .subscription(VisualBoard::subscription)
+        .exit_on_close_request(false)
+        .window_size(WINDOW_SIZE)
+        .run_with(move || {
+            let mut visual_board = VisualBoard::default();
+            if let Some(session) = Session::load() {
+                visual_board.restore_session(session);
+            }
+            if let Some(path) = args.assets {
+                visual_board.set_assets_dir(path);
+            }
+            if let Some(seed) = args.random_position {
+                visual_board.load_random_position(seed);
+            }
+            if let Some(seed) = args.bot_seed {
+                visual_board.seed_bot(seed);
+            }
+            let engine_path = args.engine.clone().or_else(|| visual_board.settings.engine_path.clone());
+            let start_engine = match engine_path {
+                Some(path) => visual_board.start_engine(path, false),
+                None => Task::none(),
+            };
+            (visual_board, start_engine)
         })
-        .run()
 }
 
-fn coord_to_square(x: usize, y: usize) -> Square {
-    Square::index(63 - (y * 8 + (7-x)))
-}
+const GIF_TILE_SIZE: u32 = 64;
+const WINDOW_SIZE: Size = Size {
+    width: 1280.0,
+    height: 720.0,
+};
+const MIN_BOARD_WIDTH: f32 = 240.0;
+const MIN_PANEL_WIDTH: f32 = 320.0;
+const SPLITTER_WIDTH: f32 = 6.0;
+/// Travel and time below which a press and release on the board is a click.
+const DRAG_DISTANCE: f32 = 4.0;
+const DRAG_TIME: Duration = Duration::from_millis(120);
+const TAKEBACK_DISABLED: &str = "Takeback disabled in this mode";
+const FLASH_DURATION: Duration = Duration::from_millis(600);
+/// How long the newest move of a watched PGN takes to slide into place.
+const SLIDE_DURATION: Duration = Duration::from_millis(250);
+/// How often a watched PGN file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// How long an automatic promotion can be changed for.
+const AUTO_PROMOTION_TOAST: Duration = Duration::from_secs(3);
+const MATCH_PGN_PATH: &str = "engine-match.pgn";
+// engine games that drag on this long are scored as draws
+const MATCH_MAX_PLIES: usize = 400;
+const BENCH_GAMES: u32 = 10;
+const BENCH_DEPTH: u32 = 4;
+/// Search depth used to tell whether a position has a single good move.
+const ONLY_MOVE_DEPTH: u32 = 3;
+
+fn coord_to_square(x: usize, y: usize) -> Square {
+    Square::index(63 - (y * 8 + (7-x)))
+}
+
+fn index_to_coord(index: usize) -> (usize, usize) {
+    let x = index % 8;
+    let y = 7 - (index / 8);
+    (x, y)
+}
+
+fn shortcut(key: keyboard::Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    match key.as_ref() {
+        keyboard::Key::Character("c") if modifiers.command() => Some(Message::CopyFen),
+        keyboard::Key::Character("v") if modifiers.command() => Some(Message::PasteFen),
+        keyboard::Key::Character("f") if modifiers.is_empty() => Some(Message::FlipBoard),
+        keyboard::Key::Character("m") if modifiers.is_empty() => Some(Message::MinimalViewToggled),
+        keyboard::Key::Named(keyboard::key::Named::Home) => Some(Message::JumpToStart),
+        keyboard::Key::Named(keyboard::key::Named::End) => Some(Message::JumpToEnd),
+        keyboard::Key::Named(keyboard::key::Named::PageUp) => Some(Message::StepPlies(-5)),
+        keyboard::Key::Named(keyboard::key::Named::PageDown) => Some(Message::StepPlies(5)),
+        keyboard::Key::Named(keyboard::key::Named::Enter) => Some(Message::MoveNumberEntered),
+        keyboard::Key::Named(keyboard::key::Named::ArrowUp) => Some(Message::MoveInputRecall(true)),
+        keyboard::Key::Named(keyboard::key::Named::ArrowDown) => Some(Message::MoveInputRecall(false)),
+        keyboard::Key::Named(keyboard::key::Named::ArrowLeft) => Some(Message::KeyboardCursorMoved(-1, 0)),
+        keyboard::Key::Named(keyboard::key::Named::ArrowRight) => Some(Message::KeyboardCursorMoved(1, 0)),
+        keyboard::Key::Named(keyboard::key::Named::Space) if modifiers.is_empty() => Some(Message::KeyboardCursorPressed),
+        keyboard::Key::Character(digit) if modifiers.is_empty() => digit.parse().ok().map(Message::MoveNumberDigit),
+        _ => None,
+    }
+}
+
+/// Keys picking a piece in the promotion picker, or closing it.
+fn promotion_shortcut(key: keyboard::Key, _modifiers: keyboard::Modifiers) -> Option<Message> {
+    let piece = match key.as_ref() {
+        keyboard::Key::Named(keyboard::key::Named::Escape) => return Some(Message::PromotionCancelled),
+        keyboard::Key::Character(c) => match c.to_lowercase().as_str() {
+            "q" => Piece::Queen,
+            "r" => Piece::Rook,
+            "b" => Piece::Bishop,
+            "n" => Piece::Knight,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(Message::PromotionChosen(piece))
+}
+
+fn modifiers_event(event: iced::Event, _status: iced::event::Status, _window: window::Id) -> Option<Message> {
+    match event {
+        iced::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => Some(Message::ModifiersChanged(modifiers)),
+        _ => None,
+    }
+}
+
+/// Clicks anywhere in the window, captured by a widget or not.
+fn pointer_press_event(event: iced::Event, _status: iced::event::Status, _window: window::Id) -> Option<Message> {
+    match event {
+        iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => Some(Message::PointerPressed),
+        _ => None,
+    }
+}
+
+/// Pointer events steering the divider while it is dragged.
+fn split_drag_event(event: iced::Event, _status: iced::event::Status, _window: window::Id) -> Option<Message> {
+    match event {
+        iced::Event::Mouse(mouse::Event::CursorMoved { position }) => Some(Message::SplitDragged(position.x)),
+        iced::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => Some(Message::SplitDragEnded),
+        _ => None,
+    }
+}
+
+/// Modal question over the whole window.
+fn confirm_dialog<'a>(prompt: String, yes: Message, cancel: Message) -> Element<'a, Message> {
+    opaque(center(
+        container(column![
+            text(prompt).size(24),
+            row![
+                button("Yes").on_press(yes),
+                button("Cancel").on_press(cancel),
+            ].spacing(10),
+        ].spacing(10))
+        .padding(20)
+        .style(container::rounded_box)
+    ))
+}
+
+/// Creates `dir/stem.extension` holding `contents`, numbering the name
+/// `stem-2`, `stem-3`... rather than replacing a file already there.
+fn create_unique_file(dir: &std::path::Path, stem: &str, extension: &str, contents: &str) -> std::io::Result<PathBuf> {
+    use std::io::Write;
+
+    std::fs::create_dir_all(dir)?;
+    for counter in 1.. {
+        let name = if counter == 1 { format!("{stem}.{extension}") } else { format!("{stem}-{counter}.{extension}") };
+        let path = dir.join(name);
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(contents.as_bytes())?;
+                return Ok(path);
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!()
+}
+
+/// The current UTC time as `YYYY-MM-DD_HH-MM-SS`, for file names.
+fn timestamp() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // civil date from days since 1970-01-01, counting in 400 year eras from 0000-03-01
+    let days = days as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}", time / 3600, time / 60 % 60, time % 60)
+}
+
+/// `name` with anything that can't safely go in a file name replaced.
+fn file_name_part(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+fn append_to_file(path: &str, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(contents.as_bytes())
+}
+
+/// Moves tried from a position of the game, kept apart from the game itself.
+#[derive(Debug, Clone)]
+struct Variation {
+    /// Number of game moves played before the variation branches off.
+    branch_ply: usize,
+    moves: Vec<Move>,
+}
+
+/// Timing of the board's redraws, measured from `draw`.
+#[derive(Debug, Clone, Copy, Default)]
+struct FrameStats {
+    last_frame: Option<Instant>,
+    /// Time between the last two redraws.
+    interval: Duration,
+    /// Time spent building the board geometry in the last redraw.
+    draw_time: Duration,
+}
+
+/// The game set aside while playing on from one of its positions.
+#[derive(Debug, Clone)]
+struct SavedGame {
+    start: Board,
+    moves: Vec<Move>,
+    move_clocks: Vec<Option<Duration>>,
+    annotations: BTreeMap<usize, Annotations>,
+    variation: Option<Variation>,
+    game_end: Option<GameEnd>,
+    clock: Option<Clock>,
+    dirty: bool,
+    flipped: bool,
+    vs_bot: bool,
+    vs_engine: bool,
+    bot_color: cozy_chess::Color,
+}
+
+#[derive(Debug, PartialEq)]
+enum State {
+    Playing,
+    Waiting,
+    Promoting,
+    Puzzle,
+    GameOver(GameEnd),
+}
+
+struct VisualBoard {
+    tile_size: f32,
+    dark_color: Color,
+    light_color: Color,
+    cache: Cache,
+    board: Board,
+    selected: Option<Square>,
+    promotion_square: Option<Square>,
+    state: State,
+    hovered_tile: Option<(usize, usize)>,
+    last_move: Option<Move>,
+    settings: Settings,
+    debug_tools: bool,
+    /// FEN of the position before the last change to the board, and of the
+    /// current one, tracked while debug tools are on.
+    previous_fen: Option<String>,
+    current_fen: String,
+    notice: Option<String>,
+    start_position: Board,
+    move_list: Vec<Move>,
+    gif_delay_ms: u32,
+    mobility: Mobility,
+    imbalance: Imbalance,
+    analysis: Option<(Board, SearchResult)>,
+    analyzing: bool,
+    flipped: bool,
+    vs_bot: bool,
+    /// The bot's moves come from the loaded UCI engine instead of the built-in search.
+    vs_engine: bool,
+    bot_color: cozy_chess::Color,
+    puzzle: Option<PuzzleSession>,
+    /// A rejected move, shown in red for a moment.
+    flash: Option<(Move, Instant)>,
+    show_shortcuts: bool,
+    audio: Audio,
+    draw_offered_by: Option<cozy_chess::Color>,
+    /// How the game ended, kept while a variation is explored from it.
+    game_end: Option<GameEnd>,
+    engine: Option<Engine>,
+    show_engine_options: bool,
+    engine_match: Option<EngineMatch>,
+    match_games: u32,
+    match_movetime_ms: u32,
+    /// Line being explored from a past position, if any.
+    variation: Option<Variation>,
+    /// Tints the squares of `bitboard_view`, for debugging move generation.
+    debug_overlay: bool,
+    bitboard_view: BitboardView,
+    /// Moves were played since the game was last saved.
+    dirty: bool,
+    /// Action waiting for the user to agree to discard the unsaved game.
+    pending_discard: Option<Message>,
+    clock: Option<Clock>,
+    /// Time the mover had left after each move of `move_list`, when the clock was on.
+    move_clocks: Vec<Option<Duration>>,
+    assets_dir: PathBuf,
+    /// Piece images that couldn't be found, reported in the panel.
+    missing_assets: Vec<PathBuf>,
+    drill: Option<Drill>,
+    /// Opening line typed in for the next drill.
+    drill_input: String,
+    drill_side: cozy_chess::Color,
+    /// Evaluation of each position of the game, computed on request.
+    eval_graph: Option<Vec<i32>>,
+    /// Game moves being evaluated for the graph in the background.
+    computing_eval_graph: bool,
+    /// Message starting an engine game, held until the saved engine is running again.
+    waiting_for_engine: Option<Message>,
+    /// Save waiting for confirmation, as (target, file already holding the game).
+    pending_duplicate_save: Option<(PathBuf, PathBuf)>,
+    /// Arrows and circles of game positions, keyed by ply.
+    annotations: BTreeMap<usize, Annotations>,
+    /// Square where a right-button drag started.
+    right_drag_from: Option<Square>,
+    bot: BotConfig,
+    /// Every random choice of the bot is drawn from this, seeded by `bot.seed`.
+    bot_rng: StdRng,
+    /// Mouse position over the board.
+    cursor_position: Option<Point>,
+    /// Only the board is shown, scaled to the window.
+    minimal_view: bool,
+    window_size: Size,
+    /// Move queued while the bot thinks, played on our turn if still legal.
+    premove: Option<Move>,
+    /// The selected piece follows the cursor while the left button is held.
+    dragging: bool,
+    /// The divider between board and panel is being dragged.
+    split_dragging: bool,
+    /// Digits of a move number typed to jump to it, confirmed with Enter.
+    typed_move_number: String,
+    /// Shared with every running built-in search, set to stop them all.
+    search_stop: Arc<AtomicBool>,
+    /// Position where the user stopped the bot, which then waits for them.
+    bot_stopped_at: Option<u64>,
+    /// Choices being made in the New Game dialog while it is open.
+    new_game_setup: Option<GameSetup>,
+    /// Piece images tinted from a neutral image, for sets without one per color.
+    tinted_pieces: Vec<(cozy_chess::Color, Piece, image::Handle)>,
+    /// The tinting fallback was reported, which is done only once.
+    tint_warned: bool,
+    /// Move being typed into the move box, in SAN or UCI notation.
+    move_input: String,
+    /// Moves submitted from the move box, oldest first.
+    move_input_history: Vec<String>,
+    /// Entry of `move_input_history` recalled with Up and Down, if any.
+    move_input_recall: Option<usize>,
+    /// The move box has the keyboard, so Up and Down recall its history.
+    /// Set by typing into it or clicking it, cleared by a click anywhere else.
+    move_input_focused: bool,
+    /// The pointer is over the move box, telling which clicks focus it.
+    move_input_hovered: bool,
+    /// Modifier keys held down, read when the board is clicked.
+    modifiers: keyboard::Modifiers,
+    /// Promotion made without the picker, offered for a change for a moment.
+    auto_promotion: Option<(Move, Instant)>,
+    /// Label color as typed, applied once it parses.
+    coordinate_color_input: String,
+    /// Only-move check of the last position it was run on, by board hash.
+    only_move: Option<(u64, OnlyMove)>,
+    /// Hash of the position the only-move check is running on.
+    only_move_pending: Option<u64>,
+    /// Game being reviewed before playing on from one of its positions,
+    /// brought back by Back to game.
+    saved_game: Option<SavedGame>,
+    /// Shows redraw timings over the board.
+    show_diagnostics: bool,
+    /// Updated while drawing, which only gets `&self`.
+    frame_stats: Cell<FrameStats>,
+    /// Games of the last PGN file opened, offered in a picker.
+    pgn_collection: Option<pgn::Collection>,
+    /// Game of `pgn_collection` on the board.
+    collection_game: Option<pgn::GameHeader>,
+    /// Capture the opponent could answer my last move with, when it wins
+    /// enough material to warn about.
+    blunder: Option<Move>,
+    /// Where and when the last press on the board happened.
+    drag_start: Option<(Point, Instant)>,
+    /// Judgement of every game move, once the game was annotated.
+    move_reviews: Option<Vec<MoveReview>>,
+    /// Game moves being annotated in the background.
+    annotating: bool,
+    /// Name typed for saving the current position as a preset.
+    preset_name_input: String,
+    /// PGN file followed as another program writes it, with the modification
+    /// time of the version last shown.
+    watched_pgn: Option<(PathBuf, Option<std::time::SystemTime>)>,
+    /// Move whose piece is sliding from its origin, and when it started.
+    slide: Option<(Move, Instant)>,
+    /// Square picked out with the arrow keys, kept while hidden.
+    keyboard_cursor: Option<Square>,
+    /// The keyboard cursor is drawn; moving the mouse hides it until the next key press.
+    keyboard_cursor_shown: bool,
+    /// Whether moves of the current game may be taken back.
+    game_mode: GameMode,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    Clicked(Point),
+    CursorMoved(Point),
+    HighlightPresetSelected(HighlightPreset),
+    ShowEvalNumberToggled(bool),
+    DebugToolsToggled(bool),
+    PassTurn,
+    GifDelayChanged(u32),
+    ExportGif,
+    GifPathChosen(Option<PathBuf>),
+    GifExported(Result<PathBuf, String>),
+    LearnerHintsToggled(bool),
+    SearchTimeChanged(u32),
+    Analyze,
+    AnalysisFinished(Board, SearchResult),
+    VsBotToggled(bool),
+    SwapSides,
+    BotMoved(Board, SearchResult),
+    LoadPuzzles,
+    PuzzlesLoaded(Result<Vec<Puzzle>, String>),
+    NextPuzzle,
+    ExitPuzzles,
+    Tick(Instant),
+    LastMoveStyleSelected(LastMoveStyle),
+    RandomPosition,
+    CopyFen,
+    PasteFen,
+    FenPasted(Option<String>),
+    FlipBoard,
+    ShortcutsToggled,
+    SoundToggled(bool),
+    OrientationLockSelected(Option<cozy_chess::Color>),
+    NewGame,
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    EngineOptionsToggled,
+    EngineOptionChanged(String, String),
+    MatchGamesChanged(u32),
+    MatchMovetimeChanged(u32),
+    StartMatch,
+    MatchOpponentChosen(Option<PathBuf>),
+    StopMatch,
+    EngineTick(Instant),
+    CastlingMarkersToggled(bool),
+    StepBack,
+    StepForward,
+    ReturnToGame,
+    Undo,
+    DebugOverlayToggled(bool),
+    BitboardViewSelected(BitboardView),
+    SavePgn,
+    PgnPathChosen(Option<PathBuf>),
+    DiscardConfirmed,
+    DiscardCancelled,
+    Quit,
+    Takeback,
+    ClockToggled(bool),
+    ClockAnnotationsToggled(bool),
+    ThreatPreviewToggled(bool),
+    DrillLineChanged(String),
+    DrillSideSelected(cozy_chess::Color),
+    StartDrill,
+    RestartDrill,
+    ExitDrill,
+    ComputeEvalGraph,
+    EvalGraphComputed(Vec<Move>, Vec<i32>),
+    JumpToPly(usize),
+    DuplicateSaveConfirmed,
+    DuplicateSaveCancelled,
+    RightPressed(Point),
+    RightReleased(Point),
+    OpeningBookToggled(bool),
+    CursorSquareToggled(bool),
+    MinimalViewToggled,
+    WindowResized(Size),
+    GhostArrowsToggled(bool),
+    GhostArrowFilterSelected(PieceFilter),
+    CapturesOnlyToggled(bool),
+    CopyMoves,
+    BoardMarginChanged(f32),
+    MoveTrailToggled(bool),
+    MoveTrailLengthChanged(u32),
+    AnimationsToggled(bool),
+    AnimationSpeedChanged(f32),
+    Released(Point),
+    ShredderFenToggled(bool),
+    PromotionChosen(Piece),
+    PromotionCancelled,
+    CheckingMovesToggled(bool),
+    SplitDragStarted,
+    SplitDragged(f32),
+    SplitDragEnded,
+    JumpToStart,
+    JumpToEnd,
+    StepPlies(isize),
+    MoveNumberDigit(u8),
+    MoveNumberEntered,
+    StopSearch,
+    NewGameClockToggled(bool),
+    NewGameBaseChanged(u32),
+    NewGameIncrementChanged(u32),
+    NewGameColorSelected(cozy_chess::Color),
+    NewGameOpponentSelected(Opponent),
+    StartGame,
+    NewGameCancelled,
+    MoveInputChanged(String),
+    MoveInputSubmitted,
+    /// Up (`true`) or Down (`false`) pressed outside of a text box that captures it.
+    MoveInputRecall(bool),
+    MoveInputHovered(bool),
+    PointerPressed,
+    ModifiersChanged(keyboard::Modifiers),
+    PromotionBehaviorSelected(PromotionBehavior),
+    /// Takes back the automatic promotion and opens the picker instead.
+    ChangeAutoPromotion,
+    TeachingCoordinatesToggled(bool),
+    CoordinateColorChanged(String),
+    PlayOnFromHere,
+    BackToGame,
+    DiagnosticsToggled(bool),
+    LoadPgn,
+    PgnCollectionLoaded(Option<Result<pgn::Collection, String>>),
+    CollectionGameSelected(pgn::GameHeader),
+    CollectionClosed,
+    BlunderWarningToggled(bool),
+    BlunderThresholdChanged(u32),
+    InputModeSelected(InputMode),
+    AnnotateGame,
+    /// The moves that were annotated, with their judgements.
+    GameAnnotated(Vec<Move>, Vec<MoveReview>),
+    ChooseEngine,
+    EngineChosen(Option<PathBuf>),
+    /// The engine at the path finished its handshake, or failed to. It
+    /// becomes the saved engine when the flag is set.
+    EngineStarted(PathBuf, bool, Result<StartedEngine, String>),
+    ClearEngine,
+    DefendedToggled(bool),
+    AutoSaveToggled(bool),
+    AutoSaveDirChosen(Option<PathBuf>),
+    MoveSoundsSelected(MoveSounds),
+    NewGamePresetSelected(PositionPreset),
+    DeletePreset(String),
+    PresetNameChanged(String),
+    SavePreset,
+    WatchPgn,
+    WatchPgnChosen(Option<PathBuf>),
+    StopWatching,
+    WatchTick(Instant),
+    ImbalanceToggled(bool),
+    /// Steps the keyboard cursor by this many tiles, as seen on screen.
+    KeyboardCursorMoved(i32, i32),
+    KeyboardCursorPressed,
+    NewGameModeSelected(GameMode),
+    OnlyMoveChecked(u64, OnlyMove),
+}
+
+impl VisualBoard {
+    fn update(&mut self, message: Message) -> Task<Message> {
+        self.cache.clear();
+
+        match message {
+            Message::Clicked(point) => {
+                self.keyboard_cursor_shown = false;
+                self.blunder = None;
+                match self.state {
+                    State::Playing | State::Puzzle => {
+                        let target = self.selected.zip(self.square_from_point(point))
+                            .filter(|_| self.settings.input_mode != InputMode::Drag);
+                        if let Some((from, to)) = target {
+                            if self.move_selected(from, to) {
+                                return Task::none();
+                            }
+                        }
+                        self.selected = self.square_from_point(point);
+                        self.dragging = self.settings.input_mode != InputMode::ClickClick
+                            && self.selected.is_some_and(|square| self.board.colors(self.board.side_to_move()).has(square));
+                        self.drag_start = Some((point, Instant::now()));
+                    },
+                    State::Waiting => self.queue_premove(point),
+                    State::GameOver(_) => {},
+                    State::Promoting => {
+                        let tile = self.square_from_point(point).map(|square| self.tile_of(square));
+                        let choice = self.promotion_choices().into_iter().find(|&(choice_tile, _)| Some(choice_tile) == tile);
+                        match choice {
+                            Some((_, piece)) => {
+                                self.promote(piece);
+                                self.selected = None;
+                            },
+                            None => self.cancel_promotion(),
+                        }
+                    },
+                }
+            },
+            Message::Released(point) => {
+                if !self.dragging {
+                    return Task::none();
+                }
+                self.dragging = false;
+
+                // a short press that barely moved selects the piece, as a click would
+                let clicked = self.settings.input_mode == InputMode::Both
+                    && self.drag_start.is_some_and(|(start, pressed)| {
+                        start.distance(point) < DRAG_DISTANCE || pressed.elapsed() < DRAG_TIME
+                    });
+                if clicked {
+                    return Task::none();
+                }
+
+                // dropping anywhere but on a legal square puts the piece back
+                let target = self.selected.zip(self.square_from_point(point));
+                match target.filter(|&(from, to)| self.legal_move(from, to).is_some()) {
+                    Some((from, to)) => {
+                        if self.move_selected(from, to) {
+                            return Task::none();
+                        }
+                        self.selected = Some(to);
+                    },
+                    // without clicks to finish the move, a failed drop lets go of the piece
+                    None if self.settings.input_mode == InputMode::Drag => self.selected = None,
+                    None => {},
+                }
+            },
+            Message::PromotionChosen(piece) => {
+                if self.state == State::Promoting {
+                    self.promote(piece);
+                }
+            },
+            Message::PromotionCancelled => {
+                if self.state == State::Promoting {
+                    self.cancel_promotion();
+                }
+            },
+            Message::CursorMoved(point) => {
+                let (square_x, square_y) = self.canvas_coord_to_square_coord(point);
+                self.hovered_tile = if square_x >= 8.0 || square_x < 0.0 || square_y >= 8.0  || square_y < 0.0 {
+                    None
+                } else {
+                    Some((square_x as usize, square_y as usize))
+                };
+                self.cursor_position = self.hovered_tile.map(|_| point);
+                self.keyboard_cursor_shown = false;
+            },
+            Message::HighlightPresetSelected(preset) => {
+                self.settings.highlight_colors = preset.colors();
+                self.save_settings();
+            },
+            Message::ShowEvalNumberToggled(show) => {
+                self.settings.show_eval_number = show;
+                self.save_settings();
+            },
+            Message::ImbalanceToggled(show) => {
+                self.settings.show_imbalance = show;
+                self.save_settings();
+            },
+            Message::DebugToolsToggled(enabled) => {
+                self.debug_tools = enabled;
+            },
+            Message::PassTurn => {
+                // null moves are a debugging aid only, never part of a real game
+                if self.debug_tools {
+                    self.pass_turn();
+                }
+            },
+            Message::GifDelayChanged(delay_ms) => {
+                self.gif_delay_ms = delay_ms;
+            },
+            Message::ExportGif => {
+                return Task::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .add_filter("GIF", &["gif"])
+                            .set_file_name("game.gif")
+                            .save_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::GifPathChosen,
+                );
+            },
+            Message::GifPathChosen(Some(path)) => {
+                let colors = BoardColors {
+                    light: self.light_color,
+                    dark: self.dark_color,
+                    last_move: self.settings.highlight_colors.last_move,
+                    coordinates: self.settings.teaching_coordinates.then_some(self.settings.coordinate_color),
+                };
+                let start = self.start_position.clone();
+                let moves = self.move_list.clone();
+                let delay_ms = self.gif_delay_ms;
+                let assets_dir = self.assets_dir.clone();
+
+                return Task::perform(
+                    async move {
+                        let renderer = BoardRenderer::new(&assets_dir, GIF_TILE_SIZE, colors).map_err(|err| err.to_string())?;
+                        render::export_gif(&path, start, &moves, &renderer, delay_ms).map_err(|err| err.to_string())?;
+                        Ok(path)
+                    },
+                    Message::GifExported,
+                );
+            },
+            Message::GifPathChosen(None) => {},
+            Message::GifExported(result) => {
+                self.notice = Some(match result {
+                    Ok(path) => format!("Saved GIF to {}", path.display()),
+                    Err(err) => format!("GIF export failed: {err}"),
+                });
+            },
+            Message::LearnerHintsToggled(enabled) => {
+                self.settings.learner_hints = enabled;
+                self.save_settings();
+            },
+            Message::SearchTimeChanged(search_time_ms) => {
+                self.settings.search_time_ms = search_time_ms;
+                self.save_settings();
+            },
+            Message::Analyze => {
+                self.analyzing = true;
+                let board = self.board.clone();
+                let time_limit = Duration::from_millis(self.settings.search_time_ms as u64);
+
+                return Task::perform(
+                    search::search_in_background(board.clone(), time_limit, search::MAX_DEPTH, self.search_stop.clone()),
+                    move |result| Message::AnalysisFinished(board, result),
+                );
+            },
+            Message::AnalysisFinished(board, result) => {
+                // a stopped analysis shows nothing
+                if self.analyzing {
+                    self.analyzing = false;
+                    self.analysis = Some((board, result));
+                }
+            },
+            Message::StopSearch => {
+                self.stop_engine_reply();
+                self.search_stop.store(true, Ordering::Relaxed);
+                self.search_stop = Arc::new(AtomicBool::new(false));
+                self.analyzing = false;
+                // the bot's answer is ignored once it arrives outside of Waiting
+                if self.state == State::Waiting {
+                    self.state = State::Playing;
+                    self.bot_stopped_at = Some(self.board.hash());
+                }
+            },
+            Message::VsBotToggled(enabled) => {
+                if !enabled {
+                    self.stop_engine_reply();
+                }
+                self.vs_bot = enabled;
+                self.vs_engine = false;
+                self.premove = None;
+                self.bot_stopped_at = None;
+                self.orient_for_bot_game();
+                if !enabled && self.state == State::Waiting {
+                    self.state = State::Playing;
+                }
+            },
+            Message::SwapSides => {
+                self.stop_engine_reply();
+                self.bot_color = !self.bot_color;
+                self.orient_for_bot_game();
+                self.selected = None;
+                self.premove = None;
+                self.bot_stopped_at = None;
+                // a search still running for the old bot side gets ignored on arrival
+                if self.state == State::Waiting {
+                    self.state = State::Playing;
+                }
+            },
+            Message::BotMoved(board, result) => {
+                let still_relevant = self.state == State::Waiting
+                    && self.board.side_to_move() == self.bot_color
+                    && board.same_position(&self.board);
+
+                if still_relevant {
+                    self.state = State::Playing;
+                    if let Some(mv) = result.best_move {
+                        self.play_move(mv);
+                    }
+                    self.play_premove();
+                }
+            },
+            Message::LoadPuzzles => {
+                if self.ask_discard(Message::LoadPuzzles) {
+                    return Task::none();
+                }
+                return Task::perform(
+                    async {
+                        let handle = rfd::AsyncFileDialog::new()
+                            .add_filter("Puzzles", &["txt"])
+                            .pick_file()
+                            .await?;
+                        let contents = String::from_utf8(handle.read().await).map_err(|err| err.to_string());
+                        Some(contents.and_then(|contents| puzzle::parse_puzzles(&contents)))
+                    },
+                    |result| match result {
+                        Some(result) => Message::PuzzlesLoaded(result),
+                        None => Message::PuzzlesLoaded(Ok(Vec::new())),
+                    },
+                );
+            },
+            Message::PuzzlesLoaded(Ok(puzzles)) => {
+                if !puzzles.is_empty() {
+                    self.vs_bot = false;
+                    self.drill = None;
+                    self.puzzle = Some(PuzzleSession::new(puzzles));
+                    self.start_puzzle();
+                }
+            },
+            Message::PuzzlesLoaded(Err(err)) => {
+                self.notice = Some(format!("Couldn't load puzzles: {err}"));
+            },
+            Message::NextPuzzle => {
+                if let Some(session) = &mut self.puzzle {
+                    if session.advance() {
+                        self.start_puzzle();
+                    } else {
+                        self.notice = Some("No more puzzles".to_owned());
+                    }
+                }
+            },
+            Message::ExitPuzzles => {
+                self.puzzle = None;
+                self.state = State::Playing;
+                self.notice = None;
+            },
+            Message::Tick(now) => {
+                if self.flash.is_some_and(|(_, started)| now.duration_since(started) >= self.animation_duration(FLASH_DURATION)) {
+                    self.flash = None;
+                }
+                if self.slide.is_some_and(|(_, started)| now.duration_since(started) >= self.animation_duration(SLIDE_DURATION)) {
+                    self.slide = None;
+                }
+                if self.auto_promotion.is_some_and(|(_, made)| now.duration_since(made) >= AUTO_PROMOTION_TOAST) {
+                    self.auto_promotion = None;
+                }
+                self.tick_clock(now);
+            },
+            Message::LastMoveStyleSelected(style) => {
+                self.settings.last_move_style = style;
+                self.save_settings();
+            },
+            Message::RandomPosition => {
+                if self.debug_tools && !self.ask_discard(Message::RandomPosition) {
+                    self.load_random_position(rand::random());
+                }
+            },
+            Message::CopyFen => {
+                return clipboard::write(fen::write_fen(&self.board, self.settings.shredder_fen));
+            },
+            Message::CopyMoves => {
+                let mut movetext = san::move_list_text(&self.start_position, &self.current_line());
+                // the result belongs to the game, not to a line explored from it
+                if let Some(end) = self.game_end.as_ref().filter(|_| self.variation.is_none()) {
+                    if !movetext.is_empty() {
+                        movetext.push(' ');
+                    }
+                    movetext += end.result();
+                }
+                return clipboard::write(movetext);
+            },
+            Message::PasteFen => {
+                return clipboard::read().map(Message::FenPasted);
+            },
+            Message::FenPasted(Some(contents)) => {
+                if self.ask_discard(Message::FenPasted(Some(contents.clone()))) {
+                    return Task::none();
+                }
+
+                // a slash in the first word means a FEN rather than movetext
+                let fen = contents.trim();
+                if fen.split_whitespace().next().is_some_and(|placement| placement.contains('/') && !placement.starts_with('[')) {
+                    match fen::parse_fen(fen) {
+                        Ok(board) => {
+                            self.load_position(board);
+                            self.notice = None;
+                        },
+                        Err(problems) => self.notice = Some(format!("Invalid FEN: {}", problems.join("; "))),
+                    }
+                    return Task::none();
+                }
+
+                match pgn::read_game(&contents) {
+                    Ok(game) => self.load_game(game),
+                    Err(err) => self.notice = Some(format!("Clipboard doesn't hold a valid FEN or PGN: {err}")),
+                }
+            },
+            Message::FenPasted(None) => {},
+            Message::FlipBoard => {
+                self.flipped = !self.flipped;
+            },
+            Message::ShortcutsToggled => {
+                self.show_shortcuts = !self.show_shortcuts;
+            },
+            Message::SoundToggled(enabled) => {
+                self.settings.sound_enabled = enabled;
+                self.save_settings();
+            },
+            Message::OrientationLockSelected(lock) => {
+                self.settings.orientation_lock = lock;
+                self.save_settings();
+            },
+            Message::NewGame => {
+                self.new_game_setup = Some(self.settings.game_setup.clone());
+            },
+            Message::NewGameClockToggled(enabled) => {
+                if let Some(setup) = &mut self.new_game_setup {
+                    setup.clock = enabled.then_some((clock::BASE_TIME, clock::INCREMENT));
+                }
+            },
+            Message::NewGameBaseChanged(minutes) => {
+                if let Some((base, _)) = self.new_game_setup.as_mut().and_then(|setup| setup.clock.as_mut()) {
+                    *base = Duration::from_secs(minutes as u64 * 60);
+                }
+            },
+            Message::NewGameIncrementChanged(seconds) => {
+                if let Some((_, increment)) = self.new_game_setup.as_mut().and_then(|setup| setup.clock.as_mut()) {
+                    *increment = Duration::from_secs(seconds as u64);
+                }
+            },
+            Message::NewGameColorSelected(color) => {
+                if let Some(setup) = &mut self.new_game_setup {
+                    setup.human_color = color;
+                }
+            },
+            Message::NewGameOpponentSelected(opponent) => {
+                if let Some(setup) = &mut self.new_game_setup {
+                    setup.opponent = opponent;
+                }
+            },
+            Message::NewGameModeSelected(mode) => {
+                if let Some(setup) = &mut self.new_game_setup {
+                    setup.mode = mode;
+                }
+            },
+            Message::NewGamePresetSelected(preset) => {
+                if let Some(setup) = &mut self.new_game_setup {
+                    setup.start_fen = preset.fen;
+                }
+            },
+            Message::DeletePreset(name) => {
+                self.settings.position_presets.retain(|preset| preset.name != name);
+                self.save_settings();
+            },
+            Message::StartGame => {
+                let Some(setup) = self.new_game_setup.clone() else {
+                    return Task::none();
+                };
+                if setup.opponent == Opponent::Engine && self.engine.is_none() {
+                    return self.restart_engine_for(Message::StartGame).unwrap_or_else(Task::none);
+                }
+                if self.ask_discard(Message::StartGame) {
+                    return Task::none();
+                }
+                self.new_game_setup = None;
+                self.start_game(setup);
+            },
+            Message::NewGameCancelled => {
+                self.new_game_setup = None;
+            },
+            Message::Resign => {
+                if self.is_hotseat() && self.state == State::Playing {
+                    self.end_game(GameEnd::Resignation(self.board.side_to_move()));
+                    self.auto_save();
+                }
+            },
+            Message::OfferDraw => {
+                if self.is_hotseat() && self.state == State::Playing {
+                    self.draw_offered_by = Some(self.board.side_to_move());
+                }
+            },
+            Message::AcceptDraw => {
+                if self.draw_offered_by.is_some() && self.state == State::Playing {
+                    self.end_game(GameEnd::Agreement);
+                    self.auto_save();
+                }
+            },
+            Message::DeclineDraw => {
+                self.draw_offered_by = None;
+            },
+            Message::EngineOptionsToggled => {
+                self.show_engine_options = !self.show_engine_options;
+            },
+            Message::EngineOptionChanged(name, value) => {
+                if let Some(engine) = &mut self.engine {
+                    match engine.set_option(&name, &value) {
+                        // pressing a button is a one-off, never replayed on the next start
+                        Ok(()) if engine.is_button(&name) => {},
+                        Ok(()) => {
+                            let path = engine.path.display().to_string();
+                            self.settings.set_engine_option(&path, &name, &value);
+                            self.save_settings();
+                        },
+                        Err(err) => self.notice = Some(format!("Engine error: {err}")),
+                    }
+                }
+            },
+            Message::MatchGamesChanged(games) => {
+                self.match_games = games;
+            },
+            Message::MatchMovetimeChanged(movetime_ms) => {
+                self.match_movetime_ms = movetime_ms;
+            },
+            Message::StartMatch => {
+                if let Some(task) = self.restart_engine_for(Message::StartMatch) {
+                    return task;
+                }
+                if self.ask_discard(Message::StartMatch) {
+                    return Task::none();
+                }
+                return Task::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Choose the opponent engine")
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::MatchOpponentChosen,
+                );
+            },
+            Message::MatchOpponentChosen(Some(path)) => {
+                match Engine::start(&path) {
+                    Ok(opponent) => {
+                        let movetime = Duration::from_millis(self.match_movetime_ms as u64);
+                        self.engine_match = Some(EngineMatch::new(opponent, self.match_games, movetime));
+                        self.vs_bot = false;
+                        self.puzzle = None;
+                        self.drill = None;
+                        self.start_match_game();
+                    },
+                    Err(err) => self.notice = Some(format!("Couldn't start opponent engine: {err}")),
+                }
+            },
+            Message::MatchOpponentChosen(None) => {},
+            Message::StopMatch => {
+                self.stop_match();
+            },
+            Message::EngineTick(_) => {
+                if self.engine_match.is_some() {
+                    self.poll_match_engine();
+                } else {
+                    self.poll_opponent_engine();
+                }
+            },
+            Message::CastlingMarkersToggled(show) => {
+                self.settings.show_castling_markers = show;
+                self.save_settings();
+            },
+            Message::StepBack => {
+                if self.can_review() {
+                    match &mut self.variation {
+                        Some(variation) if variation.moves.is_empty() => {
+                            variation.branch_ply = variation.branch_ply.saturating_sub(1);
+                        },
+                        Some(_) => {},
+                        None if !self.move_list.is_empty() => {
+                            self.variation = Some(Variation {
+                                branch_ply: self.move_list.len() - 1,
+                                moves: Vec::new(),
+                            });
+                        },
+                        None => {},
+                    }
+                    self.replay();
+                }
+            },
+            Message::StepForward => {
+                if let Some(variation) = self.variation.as_mut().filter(|variation| variation.moves.is_empty()) {
+                    variation.branch_ply += 1;
+                    if variation.branch_ply >= self.move_list.len() {
+                        self.variation = None;
+                    }
+                    self.replay();
+                }
+            },
+            Message::ReturnToGame => {
+                self.variation = None;
+                self.replay();
+            },
+            Message::Undo => {
+                match &mut self.variation {
+                    Some(variation) => {
+                        variation.moves.pop();
+                    },
+                    None if self.is_hotseat() && self.state == State::Playing => {
+                        if !self.game_mode.allow_takeback() {
+                            self.notice = Some(TAKEBACK_DISABLED.to_owned());
+                            return Task::none();
+                        }
+                        self.move_list.pop();
+                        // an offer belongs to the position it was made in
+                        self.draw_offered_by = None;
+                        self.dirty = true;
+                    },
+                    None => {},
+                }
+                self.replay();
+            },
+            Message::DebugOverlayToggled(enabled) => {
+                self.debug_overlay = enabled;
+            },
+            Message::BitboardViewSelected(view) => {
+                self.bitboard_view = view;
+            },
+            Message::SavePgn => {
+                return Task::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .add_filter("PGN", &["pgn"])
+                            .set_file_name("game.pgn")
+                            .save_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::PgnPathChosen,
+                );
+            },
+            Message::PgnPathChosen(Some(path)) => {
+                let folder = path.parent().unwrap_or(std::path::Path::new("."));
+                match library::find_duplicate(folder, &path, &self.start_position, &self.move_list) {
+                    Some(duplicate) => self.pending_duplicate_save = Some((path, duplicate)),
+                    None => self.write_pgn(&path),
+                }
+            },
+            Message::PgnPathChosen(None) => {},
+            Message::DiscardConfirmed => {
+                if let Some(message) = self.pending_discard.take() {
+                    self.dirty = false;
+                    // the game set aside by playing on is given up too
+                    if let Some(saved) = &mut self.saved_game {
+                        saved.dirty = false;
+                    }
+                    return self.update(message);
+                }
+            },
+            Message::DiscardCancelled => {
+                self.pending_discard = None;
+            },
+            Message::Quit => {
+                if !self.ask_discard(Message::Quit) {
+                    if let Err(err) = self.session().save() {
+                        eprintln!("failed to save session: {err}");
+                    }
+                    self.shut_down_engines();
+                    return iced::exit();
+                }
+            },
+            Message::Takeback => {
+                if self.can_take_back() && !self.game_mode.allow_takeback() {
+                    self.notice = Some(TAKEBACK_DISABLED.to_owned());
+                } else if self.can_take_back() {
+                    // the bot's reply and the move it answered, or just one
+                    // move when the bot hasn't replied yet
+                    self.move_list.pop();
+                    self.replay();
+                    if self.board.side_to_move() == self.bot_color && !self.move_list.is_empty() {
+                        self.move_list.pop();
+                        self.replay();
+                    }
+                    self.draw_offered_by = None;
+                    self.dirty = true;
+                }
+            },
+            Message::ClockToggled(enabled) => {
+                self.clock = enabled.then(|| self.new_clock());
+            },
+            Message::ClockAnnotationsToggled(enabled) => {
+                self.settings.pgn_clock_annotations = enabled;
+                self.save_settings();
+            },
+            Message::ThreatPreviewToggled(enabled) => {
+                self.settings.threat_preview = enabled;
+                self.save_settings();
+            },
+            Message::DrillLineChanged(line) => {
+                self.drill_input = line;
+            },
+            Message::DrillSideSelected(side) => {
+                self.drill_side = side;
+            },
+            Message::StartDrill => {
+                if self.ask_discard(Message::StartDrill) {
+                    return Task::none();
+                }
+
+                match pgn::read_movetext(&Board::default(), &self.drill_input) {
+                    Ok(line) if !line.is_empty() => {
+                        self.vs_bot = false;
+                        self.puzzle = None;
+                        self.drill = Some(Drill::new(line, self.drill_side));
+                        self.restart_drill();
+                    },
+                    Ok(_) => self.notice = Some("The opening line has no moves".to_owned()),
+                    Err(err) => self.notice = Some(format!("Couldn't read the opening line: {err}")),
+                }
+            },
+            Message::RestartDrill => {
+                self.restart_drill();
+            },
+            Message::ExitDrill => {
+                self.drill = None;
+                self.state = State::Playing;
+                self.notice = None;
+            },
+            Message::ComputeEvalGraph => {
+                self.computing_eval_graph = true;
+                let moves = self.move_list.clone();
+                return Task::perform(
+                    eval_graph::evaluate_in_background(self.start_position.clone(), moves.clone()),
+                    move |values| Message::EvalGraphComputed(moves, values),
+                );
+            },
+            Message::EvalGraphComputed(moves, values) => {
+                self.computing_eval_graph = false;
+                // the game may have moved on while it was being evaluated
+                if moves == self.move_list {
+                    self.eval_graph = Some(values);
+                }
+            },
+            Message::JumpToPly(ply) => {
+                if self.can_review() {
+                    self.variation = (ply < self.move_list.len()).then(|| Variation {
+                        branch_ply: ply,
+                        moves: Vec::new(),
+                    });
+                    self.replay();
+                }
+            },
+            Message::JumpToStart => {
+                if self.can_navigate_by_keyboard() {
+                    return self.update(Message::JumpToPly(0));
+                }
+            },
+            Message::JumpToEnd => {
+                if self.can_navigate_by_keyboard() {
+                    return self.update(Message::JumpToPly(self.move_list.len()));
+                }
+            },
+            Message::StepPlies(plies) => {
+                if self.can_navigate_by_keyboard() {
+                    let from = self.variation.as_ref().map_or(self.move_list.len(), |variation| variation.branch_ply);
+                    let ply = from.saturating_add_signed(plies).min(self.move_list.len());
+                    return self.update(Message::JumpToPly(ply));
+                }
+            },
+            Message::MoveNumberDigit(digit) => {
+                if self.can_navigate_by_keyboard() && self.typed_move_number.len() < 4 {
+                    self.typed_move_number += &digit.to_string();
+                }
+            },
+            Message::MoveNumberEntered => {
+                let typed = std::mem::take(&mut self.typed_move_number);
+                if let (true, Ok(number)) = (self.can_navigate_by_keyboard(), typed.parse::<usize>()) {
+                    return self.update(Message::JumpToPly(self.ply_of_move_number(number)));
+                }
+            },
+            Message::DuplicateSaveConfirmed => {
+                if let Some((path, _)) = self.pending_duplicate_save.take() {
+                    self.write_pgn(&path);
+                }
+            },
+            Message::DuplicateSaveCancelled => {
+                self.pending_duplicate_save = None;
+            },
+            Message::RightPressed(point) => {
+                self.right_drag_from = self.square_from_point(point);
+            },
+            Message::RightReleased(point) => {
+                let from = self.right_drag_from.take();
+                if let (Some(from), Some(to), Some(ply)) = (from, self.square_from_point(point), self.current_ply()) {
+                    let annotations = self.annotations.entry(ply).or_default();
+                    if from == to {
+                        annotations.toggle_square(from, MarkColor::Green);
+                    } else {
+                        annotations.toggle_arrow(from, to, MarkColor::Green);
+                    }
+                }
+            },
+            Message::OpeningBookToggled(enabled) => {
+                self.settings.use_opening_book = enabled;
+                self.save_settings();
+            },
+            Message::CheckingMovesToggled(enabled) => {
+                self.settings.show_checking_moves = enabled;
+                self.save_settings();
+            },
+            Message::CapturesOnlyToggled(enabled) => {
+                self.settings.captures_only = enabled;
+                self.save_settings();
+            },
+            Message::AnimationsToggled(enabled) => {
+                self.settings.animations = enabled;
+                // whatever is playing skips straight to its end
+                if !enabled {
+                    self.flash = None;
+                }
+                self.save_settings();
+            },
+            Message::AnimationSpeedChanged(speed) => {
+                self.settings.animation_speed = speed;
+                self.save_settings();
+            },
+            Message::MoveTrailToggled(enabled) => {
+                self.settings.move_trail = enabled;
+                self.save_settings();
+            },
+            Message::MoveTrailLengthChanged(length) => {
+                self.settings.move_trail_length = length;
+                self.save_settings();
+            },
+            Message::SplitDragStarted => {
+                self.split_dragging = true;
+            },
+            Message::SplitDragged(x) => {
+                if self.split_dragging {
+                    let max = (self.window_size.width - MIN_PANEL_WIDTH).max(MIN_BOARD_WIDTH);
+                    self.settings.panel_split = x.clamp(MIN_BOARD_WIDTH, max) / self.window_size.width;
+                    self.update_tile_size();
+                }
+            },
+            Message::SplitDragEnded => {
+                self.split_dragging = false;
+                self.save_settings();
+            },
+            Message::BoardMarginChanged(margin) => {
+                self.settings.board_margin = margin;
+                self.update_tile_size();
+                self.save_settings();
+            },
+            Message::ShredderFenToggled(enabled) => {
+                self.settings.shredder_fen = enabled;
+                self.save_settings();
+            },
+            Message::CursorSquareToggled(show) => {
+                self.settings.show_cursor_square = show;
+                self.save_settings();
+            },
+            Message::MinimalViewToggled => {
+                self.minimal_view = !self.minimal_view;
+                self.update_tile_size();
+            },
+            Message::WindowResized(size) => {
+                self.window_size = size;
+                self.update_tile_size();
+            },
+            Message::GhostArrowsToggled(enabled) => {
+                self.settings.ghost_arrows = enabled;
+                self.save_settings();
+            },
+            Message::GhostArrowFilterSelected(filter) => {
+                self.settings.ghost_arrow_filter = filter;
+                self.save_settings();
+            },
+            Message::MoveInputChanged(input) => {
+                self.move_input = input;
+                self.move_input_recall = None;
+                self.move_input_focused = true;
+            },
+            Message::MoveInputSubmitted => {
+                self.move_input_focused = true;
+                self.move_input_recall = None;
+                let input = self.move_input.trim().to_owned();
+                if input.is_empty() {
+                    return Task::none();
+                }
+                if self.move_input_history.last() != Some(&input) {
+                    self.move_input_history.push(input.clone());
+                }
+
+                let mv = san::parse_san(&self.board, &input)
+                    .or_else(|| position::parse_uci_move(&self.board, &input));
+                match mv {
+                    Some(mv) if matches!(self.state, State::Playing | State::Puzzle) => {
+                        self.selected = None;
+                        self.human_move(mv);
+                        self.move_input.clear();
+                    },
+                    Some(_) => self.notice = Some("Can't move right now".to_owned()),
+                    None => self.notice = Some(format!("Illegal move: {input}")),
+                }
+            },
+            Message::MoveInputRecall(up) => {
+                // away from the move box, Up and Down steer the keyboard cursor
+                if !self.move_input_focused {
+                    return self.update(Message::KeyboardCursorMoved(0, if up { -1 } else { 1 }));
+                }
+                if self.move_input_history.is_empty() {
+                    return Task::none();
+                }
+                let last = self.move_input_history.len() - 1;
+                self.move_input_recall = match (self.move_input_recall, up) {
+                    (None, true) => Some(last),
+                    (Some(index), true) => Some(index.saturating_sub(1)),
+                    (Some(index), false) if index < last => Some(index + 1),
+                    _ => None,
+                };
+                self.move_input = self.move_input_recall
+                    .map_or(String::new(), |index| self.move_input_history[index].clone());
+            },
+            Message::MoveInputHovered(hovered) => {
+                self.move_input_hovered = hovered;
+            },
+            Message::PointerPressed => {
+                // the text input takes focus from a click on it and drops it on any other
+                self.move_input_focused = self.move_input_hovered;
+            },
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+            },
+            Message::PromotionBehaviorSelected(behavior) => {
+                self.settings.promotion_behavior = behavior;
+                self.save_settings();
+            },
+            Message::ChangeAutoPromotion => {
+                let Some((mv, _)) = self.auto_promotion.take() else {
+                    return Task::none();
+                };
+                // only while nothing was played after it, bot replies included
+                if self.state != State::Playing || self.current_line().last() != Some(&mv) {
+                    return Task::none();
+                }
+                match &mut self.variation {
+                    Some(variation) => {
+                        variation.moves.pop();
+                    },
+                    None => {
+                        self.move_list.pop();
+                    },
+                }
+                self.replay();
+                self.selected = Some(mv.from);
+                self.promotion_square = Some(mv.to);
+                self.state = State::Promoting;
+            },
+            Message::TeachingCoordinatesToggled(enabled) => {
+                self.settings.teaching_coordinates = enabled;
+                self.save_settings();
+            },
+            Message::CoordinateColorChanged(input) => {
+                if let Some(color) = Color::parse(&input) {
+                    self.settings.coordinate_color = color;
+                    self.save_settings();
+                }
+                self.coordinate_color_input = input;
+            },
+            Message::PlayOnFromHere => {
+                if self.can_play_on() {
+                    if self.settings.game_setup.opponent == Opponent::Engine
+                        && let Some(task) = self.restart_engine_for(Message::PlayOnFromHere)
+                    {
+                        return task;
+                    }
+                    self.play_on();
+                }
+            },
+            Message::BackToGame => {
+                self.return_to_game();
+            },
+            Message::DiagnosticsToggled(enabled) => {
+                self.show_diagnostics = enabled;
+            },
+            Message::LoadPgn => {
+                return Task::perform(
+                    async {
+                        let handle = rfd::AsyncFileDialog::new()
+                            .add_filter("PGN", &["pgn"])
+                            .pick_file()
+                            .await?;
+                        let contents = String::from_utf8(handle.read().await).map_err(|err| err.to_string());
+                        // only the tags are read here, so big files open quickly
+                        Some(contents.map(|contents| pgn::read_collection(&contents)))
+                    },
+                    Message::PgnCollectionLoaded,
+                );
+            },
+            Message::PgnCollectionLoaded(Some(Ok(collection))) => {
+                match collection.games.as_slice() {
+                    [] => self.notice = Some("No games found in the file".to_owned()),
+                    // a lone game needs no picker
+                    [game] => {
+                        if self.ask_discard(Message::PgnCollectionLoaded(Some(Ok(collection.clone())))) {
+                            return Task::none();
+                        }
+                        match pgn::read_game(game) {
+                            Ok(game) => {
+                                self.load_game(game);
+                                self.pgn_collection = None;
+                                self.collection_game = None;
+                            },
+                            Err(err) => self.notice = Some(format!("Couldn't load the game: {err}")),
+                        }
+                    },
+                    _ => {
+                        self.pgn_collection = Some(collection);
+                        self.collection_game = None;
+                    },
+                }
+            },
+            Message::PgnCollectionLoaded(Some(Err(err))) => {
+                self.notice = Some(format!("Couldn't read the PGN file: {err}"));
+            },
+            Message::PgnCollectionLoaded(None) => {},
+            Message::CollectionGameSelected(header) => {
+                if self.ask_discard(Message::CollectionGameSelected(header.clone())) {
+                    return Task::none();
+                }
+                let Some(game) = self.pgn_collection.as_ref().and_then(|collection| collection.games.get(header.index)) else {
+                    return Task::none();
+                };
+                match pgn::read_game(game) {
+                    Ok(game) => {
+                        self.load_game(game);
+                        self.collection_game = Some(header);
+                    },
+                    Err(err) => self.notice = Some(format!("Couldn't load game {}: {err}", header.index + 1)),
+                }
+            },
+            Message::CollectionClosed => {
+                self.pgn_collection = None;
+                self.collection_game = None;
+            },
+            Message::BlunderWarningToggled(enabled) => {
+                self.settings.blunder_warning = enabled;
+                self.blunder = None;
+                self.save_settings();
+            },
+            Message::BlunderThresholdChanged(threshold) => {
+                self.settings.blunder_threshold = threshold;
+                self.save_settings();
+            },
+            Message::InputModeSelected(mode) => {
+                self.settings.input_mode = mode;
+                self.dragging = false;
+                self.save_settings();
+            },
+            Message::AnnotateGame => {
+                self.annotating = true;
+                let moves = self.move_list.clone();
+                return Task::perform(
+                    review::review_in_background(self.start_position.clone(), moves.clone()),
+                    move |reviews| Message::GameAnnotated(moves, reviews),
+                );
+            },
+            Message::GameAnnotated(moves, reviews) => {
+                self.annotating = false;
+                // the game may have been replaced while it was being searched
+                if moves == self.move_list {
+                    self.move_reviews = Some(reviews);
+                }
+            },
+            Message::ChooseEngine => {
+                return Task::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Choose a UCI engine")
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::EngineChosen,
+                );
+            },
+            Message::EngineChosen(Some(path)) => {
+                if self.engine_match.is_some() {
+                    self.notice = Some("Stop the match before changing engines".to_owned());
+                    return Task::none();
+                }
+                return self.start_engine(path, true);
+            },
+            Message::EngineChosen(None) => {},
+            Message::EngineStarted(path, remember, result) => {
+                match result.map(|started| started.take()) {
+                    // the match started meanwhile keeps its engine
+                    Ok(Some(_)) if self.engine_match.is_some() => {
+                        self.notice = Some("Stop the match before changing engines".to_owned());
+                    },
+                    Ok(Some(engine)) => {
+                        self.connect_engine(engine);
+                        if remember {
+                            self.settings.engine_path = Some(path);
+                            self.save_settings();
+                        }
+                        if let Some(message) = self.waiting_for_engine.take() {
+                            return self.update(message);
+                        }
+                    },
+                    Ok(None) => {},
+                    Err(err) if self.waiting_for_engine.take().is_some() => {
+                        self.notice = Some(format!("Couldn't start engine: {err}"));
+                    },
+                    Err(err) if remember => self.notice = Some(format!("Not a valid UCI engine ({err})")),
+                    Err(err) => self.notice = Some(format!("Couldn't start engine: {err}")),
+                }
+            },
+            Message::ClearEngine => {
+                self.disconnect_engine();
+                self.settings.engine_path = None;
+                self.save_settings();
+                self.notice = Some("Using the built-in bot".to_owned());
+            },
+            Message::DefendedToggled(enabled) => {
+                self.settings.show_defended = enabled;
+                self.save_settings();
+            },
+            Message::AutoSaveToggled(true) => {
+                return Task::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Choose a folder for finished games")
+                            .pick_folder()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::AutoSaveDirChosen,
+                );
+            },
+            Message::AutoSaveToggled(false) => {
+                self.settings.auto_save_dir = None;
+                self.save_settings();
+            },
+            Message::AutoSaveDirChosen(Some(dir)) => {
+                self.settings.auto_save_dir = Some(dir);
+                self.save_settings();
+            },
+            Message::AutoSaveDirChosen(None) => {},
+            Message::MoveSoundsSelected(sounds) => {
+                self.settings.move_sounds = sounds;
+                self.save_settings();
+            },
+            Message::PresetNameChanged(name) => {
+                self.preset_name_input = name;
+            },
+            Message::SavePreset => {
+                let name = self.preset_name_input.trim().to_owned();
+                if name.is_empty() {
+                    self.notice = Some("Name the position first".to_owned());
+                    return Task::none();
+                }
+                self.settings.save_preset(&name, &fen::write_fen(&self.board, false));
+                self.save_settings();
+                self.preset_name_input.clear();
+                self.notice = Some(format!("Saved position as {name}"));
+            },
+            Message::WatchPgn => {
+                return Task::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Choose a PGN file to follow")
+                            .add_filter("PGN", &["pgn"])
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::WatchPgnChosen,
+                );
+            },
+            Message::WatchPgnChosen(Some(path)) => {
+                if self.ask_discard(Message::WatchPgnChosen(Some(path.clone()))) {
+                    return Task::none();
+                }
+                // the board only mirrors the file, nobody plays on it
+                self.vs_bot = false;
+                self.clock = None;
+                self.watched_pgn = Some((path, None));
+                self.reload_watched_pgn();
+            },
+            Message::WatchPgnChosen(None) => {},
+            Message::StopWatching => {
+                self.watched_pgn = None;
+            },
+            Message::WatchTick(_) => {
+                self.reload_watched_pgn();
+            },
+            Message::KeyboardCursorMoved(dx, dy) => {
+                // the first key press only brings the cursor back where it was
+                if !self.keyboard_cursor_shown {
+                    self.keyboard_cursor_shown = true;
+                    if self.keyboard_cursor.is_none() {
+                        self.keyboard_cursor = Some(self.selected.unwrap_or_else(|| self.square_at(4, 6)));
+                    }
+                    return Task::none();
+                }
+                if let Some(square) = self.keyboard_cursor {
+                    let (x, y) = self.tile_of(square);
+                    let x = (x as i32 + dx).clamp(0, 7) as usize;
+                    let y = (y as i32 + dy).clamp(0, 7) as usize;
+                    self.keyboard_cursor = Some(self.square_at(x, y));
+                }
+            },
+            Message::KeyboardCursorPressed => {
+                let Some(square) = self.keyboard_cursor.filter(|_| self.keyboard_cursor_shown) else {
+                    return Task::none();
+                };
+                // works like clicking the square
+                if matches!(self.state, State::Playing | State::Puzzle) {
+                    self.blunder = None;
+                    if let Some(from) = self.selected {
+                        if self.move_selected(from, square) {
+                            return Task::none();
+                        }
+                    }
+                    self.selected = Some(square);
+                }
+            },
+            Message::OnlyMoveChecked(hash, only_move) => {
+                if self.only_move_pending == Some(hash) {
+                    self.only_move_pending = None;
+                }
+                self.only_move = Some((hash, only_move));
+            },
+        }
+
+        self.mobility = Mobility::of(&self.board);
+        self.imbalance = Imbalance::of(&self.board);
+        let only_move_check = self.check_only_move();
+        self.track_fen();
+
+        Task::batch([only_move_check, self.start_bot_move()])
+    }
+
+    /// Starts the debug only-move check of the board in the background,
+    /// unless it already ran or is running for this position.
+    fn check_only_move(&mut self) -> Task<Message> {
+        let hash = self.board.hash();
+        let checked = self.only_move.as_ref().is_some_and(|(checked, _)| *checked == hash);
+        if !self.debug_tools || checked || self.only_move_pending == Some(hash) {
+            return Task::none();
+        }
+
+        self.only_move_pending = Some(hash);
+        Task::perform(
+            search::only_move_in_background(self.board.clone(), ONLY_MOVE_DEPTH),
+            move |only_move| Message::OnlyMoveChecked(hash, only_move),
+        )
+    }
+
+    fn write_pgn(&mut self, path: &std::path::Path) {
+        match std::fs::write(path, self.game_pgn()) {
+            Ok(()) => {
+                self.dirty = false;
+                self.notice = Some(format!("Saved game to {}", path.display()));
+            },
+            Err(err) => self.notice = Some(format!("Couldn't save game: {err}")),
+        }
+    }
+
+    /// Writes a game that just finished on the board to the auto-save
+    /// folder, if one is set, under a name no earlier game has taken.
+    fn auto_save(&mut self) {
+        let Some(dir) = self.settings.auto_save_dir.clone() else {
+            return;
+        };
+
+        let (white, black) = self.player_names();
+        let stem = format!("{}_{}_vs_{}", timestamp(), file_name_part(&white), file_name_part(&black));
+        match create_unique_file(&dir, &stem, "pgn", &self.game_pgn()) {
+            Ok(path) => {
+                self.dirty = false;
+                self.notice = Some(format!("Saved to {}", path.display()));
+            },
+            Err(err) => self.notice = Some(format!("Couldn't auto-save game: {err}")),
+        }
+    }
+
+    /// Who plays White and Black, for PGN tags and file names.
+    fn player_names(&self) -> (String, String) {
+        if !self.vs_bot {
+            return ("Human".to_owned(), "Human".to_owned());
+        }
+
+        let bot = match &self.engine {
+            Some(engine) if self.vs_engine => engine.name.clone(),
+            _ => "chess-rs".to_owned(),
+        };
+        match self.bot_color {
+            cozy_chess::Color::White => (bot, "Human".to_owned()),
+            cozy_chess::Color::Black => ("Human".to_owned(), bot),
+        }
+    }
+
+    fn game_pgn(&self) -> String {
+        let result = self.game_end.as_ref().map_or("*", GameEnd::result);
+        let (white, black) = self.player_names();
+        let mut headers = vec![("Event", "Casual game".to_owned()), ("White", white), ("Black", black)];
+        if let Some(termination) = self.game_end.as_ref().and_then(GameEnd::termination) {
+            headers.push(("Termination", termination.to_owned()));
+        }
+        let comment = |index: usize| {
+            let clock = self.move_clocks.get(index).copied().flatten()
+                .filter(|_| self.settings.pgn_clock_annotations)
+                .map(pgn::clock_comment);
+            let marks = self.annotations.get(&(index + 1)).and_then(Annotations::to_comment);
+            // the last move explains how the game ended
+            let end = self.game_end.as_ref()
+                .filter(|_| index + 1 == self.move_list.len())
+                .map(GameEnd::description);
+            let parts: Vec<String> = [clock, marks, end].into_iter().flatten().collect();
+            (!parts.is_empty()).then(|| parts.join(" "))
+        };
+        pgn::write_game(&headers, &self.start_position, self.settings.shredder_fen, &self.move_list, comment, result)
+    }
+
+    /// The board fills the window in the minimal view and its side of the
+    /// split otherwise.
+    fn update_tile_size(&mut self) {
+        let width = if self.minimal_view { self.window_size.width } else { self.board_width() };
+        let margin = self.settings.board_margin * 2.0;
+        self.tile_size = ((width.min(self.window_size.height) - margin) / 8.0).floor().max(1.0);
+    }
+
+    /// Width left of the divider between board and panel.
+    fn board_width(&self) -> f32 {
+        (self.window_size.width * self.settings.panel_split).max(MIN_BOARD_WIDTH)
+    }
+
+    /// Holds back `message` for confirmation when it would throw away moves
+    /// that were never saved. Returns whether it was held back.
+    fn ask_discard(&mut self, message: Message) -> bool {
+        if !self.dirty && !self.saved_game.as_ref().is_some_and(|saved| saved.dirty) {
+            return false;
+        }
+        self.pending_discard = Some(message);
+        true
+    }
+
+    fn view(&self) -> Element<Message> {
+        if self.minimal_view {
+            let board = Canvas::new(self).width(Fill).height(Fill);
+            return stack![board].push_maybe(self.new_game_dialog()).push_maybe(self.confirmation()).into();
+        }
+
+        // the canvas takes all the room left of the divider, with the board centered in it
+        let board = Canvas::new(self).width(Fill).height(Fill);
+        let splitter = mouse_area(container(text("")).width(SPLITTER_WIDTH).height(Fill).style(container::rounded_box))
+            .on_press(Message::SplitDragStarted)
+            .interaction(mouse::Interaction::ResizingHorizontally);
+
+        let content = container(
+            row![
+                container(board).width(self.board_width() - SPLITTER_WIDTH).height(Fill),
+                splitter,
+                column![
+                    self.position_info(),
+                    pick_list(
+                        HighlightPreset::ALL,
+                        HighlightPreset::ALL.into_iter().find(|preset| preset.colors() == self.settings.highlight_colors),
+                        Message::HighlightPresetSelected,
+                    ).placeholder("Custom highlights"),
+                    pick_list(
+                        LastMoveStyle::ALL,
+                        Some(self.settings.last_move_style),
+                        Message::LastMoveStyleSelected,
+                    ),
+                    row![
+                        text("Margin"),
+                        slider(0.0..=40.0, self.settings.board_margin, Message::BoardMarginChanged).width(150),
+                    ].spacing(10),
+                    row![
+                        checkbox("Animations", self.settings.animations)
+                            .on_toggle(Message::AnimationsToggled),
+                        slider(0.25..=4.0, self.settings.animation_speed, Message::AnimationSpeedChanged).step(0.25).width(100),
+                        text(format!("{}x", self.settings.animation_speed)),
+                    ].spacing(10),
+                    row![
+                        checkbox("Move trail", self.settings.move_trail)
+                            .on_toggle(Message::MoveTrailToggled),
+                        slider(1..=8, self.settings.move_trail_length, Message::MoveTrailLengthChanged).width(100),
+                        text(format!("{} moves", self.settings.move_trail_length)),
+                    ].spacing(10),
+                    checkbox("Castling markers", self.settings.show_castling_markers)
+                        .on_toggle(Message::CastlingMarkersToggled),
+                    checkbox("Show square under cursor", self.settings.show_cursor_square)
+                        .on_toggle(Message::CursorSquareToggled),
+                    row![
+                        checkbox("Teaching coordinates", self.settings.teaching_coordinates)
+                            .on_toggle(Message::TeachingCoordinatesToggled),
+                    ]
+                    .push_maybe(self.settings.teaching_coordinates.then(|| {
+                        text_input("#1a1a1a", &self.coordinate_color_input)
+                            .on_input(Message::CoordinateColorChanged)
+                            .width(100)
+                    }))
+                    .spacing(10),
+                    row![
+                        checkbox("Show all moves", self.settings.ghost_arrows)
+                            .on_toggle(Message::GhostArrowsToggled),
+                        pick_list(PieceFilter::ALL, Some(self.settings.ghost_arrow_filter), Message::GhostArrowFilterSelected),
+                    ].spacing(10),
+                    row![
+                        checkbox("Sound", self.settings.sound_enabled)
+                            .on_toggle(Message::SoundToggled),
+                        pick_list(MoveSounds::ALL, Some(self.settings.move_sounds), Message::MoveSoundsSelected),
+                    ].spacing(10),
+                    checkbox("Show eval", self.settings.show_eval_number)
+                        .on_toggle(Message::ShowEvalNumberToggled),
+                    text(if self.settings.show_eval_number {
+                        format!("eval: {}", self.eval())
+                    } else {
+                        String::new()
+                    }).size(25),
+                    checkbox("Show material imbalance", self.settings.show_imbalance)
+                        .on_toggle(Message::ImbalanceToggled),
+                    text(if self.settings.show_imbalance && !self.imbalance.is_even() {
+                        self.imbalance.to_string()
+                    } else {
+                        String::new()
+                    }),
+                    checkbox("Explain illegal moves", self.settings.learner_hints)
+                        .on_toggle(Message::LearnerHintsToggled),
+                    checkbox("Show captures only", self.settings.captures_only)
+                        .on_toggle(Message::CapturesOnlyToggled),
+                    row![
+                        text("Moves by:"),
+                        pick_list(InputMode::ALL, Some(self.settings.input_mode), Message::InputModeSelected),
+                    ].spacing(10),
+                    row![
+                        text("Promotion:"),
+                        pick_list(PromotionBehavior::ALL, Some(self.settings.promotion_behavior), Message::PromotionBehaviorSelected),
+                    ].spacing(10),
+                    checkbox("Mark checking moves", self.settings.show_checking_moves)
+                        .on_toggle(Message::CheckingMovesToggled),
+                    checkbox("Show pieces the selected piece defends", self.settings.show_defended)
+                        .on_toggle(Message::DefendedToggled),
+                    checkbox("Preview threats after my move", self.settings.threat_preview)
+                        .on_toggle(Message::ThreatPreviewToggled),
+                    row![
+                        checkbox("Warn when my move hangs material", self.settings.blunder_warning)
+                            .on_toggle(Message::BlunderWarningToggled),
+                        slider(100..=900, self.settings.blunder_threshold, Message::BlunderThresholdChanged).step(50u32).width(100),
+                        text(format!("{:.1} pawns", self.settings.blunder_threshold as f32 / 100.0)),
+                    ].spacing(10),
+                    checkbox("Debug tools", self.debug_tools)
+                        .on_toggle(Message::DebugToolsToggled),
+                ]
+                .push(row![
+                    checkbox("Play vs bot", self.vs_bot).on_toggle(Message::VsBotToggled),
+                    button("Swap sides").on_press_maybe(self.vs_bot.then_some(Message::SwapSides)),
+                    checkbox("Opening book", self.settings.use_opening_book).on_toggle(Message::OpeningBookToggled),
+                ].spacing(10))
+                .push(self.game_controls())
+                .push(mouse_area(text_input("Type a move, e.g. Nf3 or g1f3", &self.move_input)
+                    .on_input(Message::MoveInputChanged)
+                    .on_submit(Message::MoveInputSubmitted)
+                    .width(400))
+                    .on_enter(Message::MoveInputHovered(true))
+                    .on_exit(Message::MoveInputHovered(false)))
+                .push(self.move_list_panel())
+                .push(self.history_controls())
+                .push(self.eval_graph_panel())
+                .push(row![
+                    text("Orientation:"),
+                    radio("Auto", None, Some(self.settings.orientation_lock), Message::OrientationLockSelected),
+                    radio("White", Some(cozy_chess::Color::White), Some(self.settings.orientation_lock), Message::OrientationLockSelected),
+                    radio("Black", Some(cozy_chess::Color::Black), Some(self.settings.orientation_lock), Message::OrientationLockSelected),
+                ].spacing(10))
+                .push(row![
+                    button("Flip board").on_press_maybe(self.settings.orientation_lock.is_none().then_some(Message::FlipBoard)),
+                    button("Keyboard shortcuts").on_press(Message::ShortcutsToggled),
+                ].spacing(10))
+                .push_maybe(self.show_shortcuts.then(|| text(
+                    "Ctrl+C     copy FEN\n\
+                     Ctrl+V     paste FEN or PGN\n\
+                     F          flip board\n\
+                     M          board only\n\
+                     Up/Down    recall typed moves\n\
+                     Home/End   first or last move\n\
+                     PgUp/PgDn  five moves back or forward\n\
+                     0-9 Enter  go to that move number\n\
+                     Arrows     move the keyboard cursor\n\
+                     Space      select or move to the cursor square\n\
+                     Shift      click to pick a promotion"
+                ).font(Font::MONOSPACE)))
+                .push(row![
+                    text(match (&self.engine, &self.settings.engine_path) {
+                        (Some(engine), _) => format!("Engine: {}", engine.name),
+                        (None, Some(path)) => format!("Engine: {} (starts with the next engine game)", path.display()),
+                        (None, None) => "Engine: built-in bot".to_owned(),
+                    }),
+                    button("Choose engine...").on_press(Message::ChooseEngine),
+                    button("Clear engine").on_press_maybe(
+                        (self.engine.is_some() || self.settings.engine_path.is_some()).then_some(Message::ClearEngine)
+                    ),
+                ].spacing(10))
+                .push_maybe(self.engine_options_panel())
+                .push_maybe(self.match_controls())
+                .push(self.puzzle_controls())
+                .push(self.drill_controls())
+                .push(self.mobility_gauge())
+                .push(row![
+                    button(if self.analyzing { "Analyzing..." } else { "Analyze" })
+                        .on_press_maybe((!self.analyzing).then_some(Message::Analyze)),
+                    slider(100..=10_000, self.settings.search_time_ms, Message::SearchTimeChanged).step(100u32).width(150),
+                    text(format!("{} ms", self.settings.search_time_ms)),
+                ].spacing(10))
+                .push_maybe(self.current_analysis().map(|result| text(format!(
+                    "best: {} {} (depth {}, {} nodes)\npv: {}",
+                    result.best_move.map_or("-".to_owned(), |mv| mv.to_string()),
+                    result.score,
+                    result.depth,
+                    result.nodes,
+                    san::move_list_text(&self.board, &result.pv),
+                ))))
+                .push(row![
+                    button("Export GIF").on_press(Message::ExportGif),
+                    slider(100..=3000, self.gif_delay_ms, Message::GifDelayChanged).step(100u32).width(150),
+                    text(format!("{} ms/move", self.gif_delay_ms)),
+                ].spacing(10))
+                .push_maybe(self.debug_tools.then(|| row![
+                    button("Pass turn").on_press(Message::PassTurn),
+                    button("Random position").on_press(Message::RandomPosition),
+                ].spacing(10)))
+                .push_maybe(self.debug_tools.then(|| row![
+                    checkbox("Bitboard overlay", self.debug_overlay).on_toggle(Message::DebugOverlayToggled),
+                    pick_list(BitboardView::ALL, Some(self.bitboard_view), Message::BitboardViewSelected),
+                    checkbox("Redraw timings", self.show_diagnostics).on_toggle(Message::DiagnosticsToggled),
+                ].spacing(10)))
+                .push_maybe((self.state == State::Waiting || self.analyzing).then(|| row![
+                    text("Engine thinking..."),
+                    button("Stop").on_press(Message::StopSearch),
+                ].spacing(10)))
+                .push_maybe(self.debug_tools.then(|| self.piece_counts()))
+                .push_maybe(self.only_move_report())
+                .push_maybe(self.fen_diff_panel())
+                .push_maybe(self.game_end_banner())
+                .push_maybe(self.notice.as_ref().map(|notice| text(notice).size(20)))
+                .push_maybe(self.auto_promotion.map(|(mv, _)| row![
+                    text(format!("Promoted to {:?}", mv.promotion.unwrap_or(Piece::Queen))),
+                    button("Change").on_press(Message::ChangeAutoPromotion),
+                ].spacing(10)))
+                .push_maybe(self.missing_assets.first().map(|path| text(format!(
+                    "Piece images not found ({} missing, e.g. {}). Point --assets at the assets directory.",
+                    self.missing_assets.len(),
+                    path.display(),
+                )).color(Color::from_rgb(0.9, 0.2, 0.2)).width(400))),
+            ].height(Fill)
+        );
+
+        stack![content].push_maybe(self.new_game_dialog()).push_maybe(self.confirmation()).into()
+    }
+
+    /// How the game ended, e.g. `Draw by threefold repetition (1/2-1/2)`.
+    fn game_end_banner(&self) -> Option<Element<Message>> {
+        let State::GameOver(end) = &self.state else {
+            return None;
+        };
+        Some(container(text(format!("{} ({})", end.description(), end.result())).size(24))
+            .padding(10)
+            .style(container::rounded_box)
+            .into())
+    }
+
+    /// Time control, colour and opponent for the next game, while being chosen.
+    fn new_game_dialog(&self) -> Option<Element<Message>> {
+        let setup = self.new_game_setup.as_ref()?;
+
+        let mut clock = column![
+            checkbox("Clock", setup.clock.is_some()).on_toggle(Message::NewGameClockToggled),
+        ].spacing(10);
+        if let Some((base, increment)) = setup.clock {
+            let minutes = (base.as_secs() / 60) as u32;
+            let seconds = increment.as_secs() as u32;
+            clock = clock
+                .push(row![
+                    slider(1..=60, minutes, Message::NewGameBaseChanged).width(150),
+                    text(format!("{minutes} min")),
+                ].spacing(10))
+                .push(row![
+                    slider(0..=30, seconds, Message::NewGameIncrementChanged).width(150),
+                    text(format!("+{seconds} s")),
+                ].spacing(10));
+        }
+
+        let color = Some(setup.human_color);
+        let missing_engine = setup.opponent == Opponent::Engine
+            && self.engine.is_none()
+            && self.settings.engine_path.is_none();
+        let presets = self.settings.presets();
+        let preset = presets.iter().find(|preset| preset.fen == setup.start_fen).cloned();
+        let deletable = preset.as_ref()
+            .filter(|preset| self.settings.position_presets.contains(preset))
+            .map(|preset| Message::DeletePreset(preset.name.clone()));
+        Some(opaque(center(
+            container(column![
+                text("New game").size(24),
+                clock,
+                row![
+                    text("Play as:"),
+                    radio("White", cozy_chess::Color::White, color, Message::NewGameColorSelected),
+                    radio("Black", cozy_chess::Color::Black, color, Message::NewGameColorSelected),
+                ].spacing(10),
+                row![
+                    text("Opponent:"),
+                    pick_list(Opponent::ALL, Some(setup.opponent), Message::NewGameOpponentSelected),
+                ].spacing(10),
+                row![
+                    text("Mode:"),
+                    pick_list(GameMode::ALL, Some(setup.mode), Message::NewGameModeSelected),
+                ].spacing(10),
+                row![
+                    text("Start from:"),
+                    pick_list(presets, preset, Message::NewGamePresetSelected).placeholder("Custom position"),
+                    button("Delete").on_press_maybe(deletable),
+                ].spacing(10),
+            ]
+            .push_maybe(missing_engine.then(|| text("Load an engine first to play against it")))
+            .push(row![
+                button("Start").on_press_maybe((!missing_engine).then_some(Message::StartGame)),
+                button("Cancel").on_press(Message::NewGameCancelled),
+            ].spacing(10))
+            .spacing(10))
+            .padding(20)
+            .style(container::rounded_box)
+        )))
+    }
+
+    /// The question waiting for an answer, if any.
+    fn confirmation(&self) -> Option<Element<Message>> {
+        if self.pending_discard.is_some() {
+            return Some(confirm_dialog("Discard current game?".to_owned(), Message::DiscardConfirmed, Message::DiscardCancelled));
+        }
+
+        self.pending_duplicate_save.as_ref().map(|(_, duplicate)| confirm_dialog(
+            format!("This game looks like a duplicate of {} — save anyway?", duplicate.display()),
+            Message::DuplicateSaveConfirmed,
+            Message::DuplicateSaveCancelled,
+        ))
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        // key presses captured by a focused text input never reach this
+        let shortcuts = keyboard::on_key_press(shortcut);
+
+        let promotion_keys = if self.state == State::Promoting {
+            keyboard::on_key_press(promotion_shortcut)
+        } else {
+            Subscription::none()
+        };
+
+        let split_drag = if self.split_dragging {
+            iced::event::listen_with(split_drag_event)
+        } else {
+            Subscription::none()
+        };
+
+        let modifiers = iced::event::listen_with(modifiers_event);
+        let pointer_presses = iced::event::listen_with(pointer_press_event);
+
+        let ticks = if self.flash.is_some() || self.slide.is_some() || self.auto_promotion.is_some() || self.clock.is_some() {
+            time::every(Duration::from_millis(50)).map(Message::Tick)
+        } else {
+            Subscription::none()
+        };
+
+        // engines answer on their own pipes, which are checked for a bestmove
+        let engine_searching = self.engine.as_ref().is_some_and(Engine::is_searching)
+            || self.engine_match.as_ref().is_some_and(|engine_match| engine_match.opponent.is_searching());
+        let engine_ticks = if engine_searching {
+            time::every(Duration::from_millis(20)).map(Message::EngineTick)
+        } else {
+            Subscription::none()
+        };
+
+        let watch_ticks = if self.watched_pgn.is_some() {
+            time::every(WATCH_INTERVAL).map(Message::WatchTick)
+        } else {
+            Subscription::none()
+        };
+
+        let close_requests = window::close_requests().map(|_| Message::Quit);
+        let resizes = window::resize_events().map(|(_, size)| Message::WindowResized(size));
+
+        Subscription::batch([shortcuts, promotion_keys, modifiers, pointer_presses, split_drag, ticks, engine_ticks, watch_ticks, close_requests, resizes])
+    }
+
+    /// Finishes the promotion waiting in the picker with `piece`.
+    fn promote(&mut self, piece: Piece) {
+        let (Some(from), Some(to)) = (self.selected, self.promotion_square.take()) else {
+            return;
+        };
+        if self.settings.last_promotion != piece {
+            self.settings.last_promotion = piece;
+            self.save_settings();
+        }
+        self.state = if self.puzzle.is_some() { State::Puzzle } else { State::Playing };
+        self.human_move(Move { from, to, promotion: Some(piece) });
+    }
+
+    /// Moves the selected piece from `selected_square` to `new_square`, or
+    /// opens the promotion picker. Returns whether the picker was opened.
+    fn move_selected(&mut self, selected_square: Square, new_square: Square) -> bool {
+        let mut promotion = None;
+         // check if move would allow promotion
+        if let Some(piece) = self.board.piece_on(selected_square) {
+            if piece == Piece::Pawn{
+                // piece is pawn so check its valid moves
+                let mut is_promotion_move = false;
+                self.board.generate_moves_for(selected_square.bitboard(), |moves| {
+                    for mv in moves {
+                        if mv.to == new_square && mv.promotion.is_some() {
+                            // this is promotion move
+                            is_promotion_move = true;
+                            return true;
+                        }
+                    }
+                    false
+                });
+
+                if is_promotion_move {
+                    promotion = self.automatic_promotion();
+                    if promotion.is_none() {
+                        self.promotion_square = Some(new_square);
+                        self.state = State::Promoting;
+                        return true;
+                    }
+                }
+            }
+        }
+       
+        // if Rank::First.bitboard().has(new_square) || Rank::Eighth.bitboard().has(new_square) 
+        // trying to move selected square to new point
+        let mv = Move {
+            from: selected_square,
+            to: new_square,
+            promotion,
+        };
+        self.human_move(mv);
+        if promotion.is_some() {
+            self.auto_promotion = Some((mv, Instant::now()));
+        }
+        false
+    }
+
+    /// Looks for a capture the opponent could answer with, now that the
+    /// human has moved, and keeps it if it wins more than the threshold.
+    fn warn_of_blunder(&mut self) {
+        let threshold = self.settings.blunder_threshold as i32;
+        self.blunder = search::best_capture(&self.board)
+            .filter(|&(_, gain)| self.settings.blunder_warning && gain >= threshold)
+            .map(|(mv, _)| mv);
+    }
+
+    /// Piece to promote to without asking, unless Shift is held to open the picker.
+    fn automatic_promotion(&self) -> Option<Piece> {
+        if self.modifiers.shift() {
+            return None;
+        }
+        match self.settings.promotion_behavior {
+            PromotionBehavior::Ask => None,
+            PromotionBehavior::AlwaysQueen => Some(Piece::Queen),
+            PromotionBehavior::RememberLast => Some(self.settings.last_promotion),
+        }
+    }
+
+    fn cancel_promotion(&mut self) {
+        self.state = if self.puzzle.is_some() { State::Puzzle } else { State::Playing };
+        self.promotion_square = None;
+    }
+
+    /// Tiles of the promotion picker with the piece each offers, queen
+    /// first, stacked from the promotion square towards the promoting side.
+    fn promotion_choices(&self) -> Vec<((usize, usize), Piece)> {
+        let Some(square) = self.promotion_square else {
+            return Vec::new();
+        };
+
+        // promotion squares are always on the top or bottom row
+        let (x, y) = self.tile_of(square);
+        [Piece::Queen, Piece::Knight, Piece::Rook, Piece::Bishop].into_iter()
+            .enumerate()
+            .map(|(i, piece)| {
+                let tile_y = if y == 0 { y + i } else { y - i };
+                ((x, tile_y), piece)
+            })
+            .collect()
+    }
+
+    /// Handles a move entered on the board by the user.
+    fn human_move(&mut self, mv: Move) {
+        if self.state != State::Puzzle {
+            if self.play_move(mv) {
+                self.warn_of_blunder();
+            }
+            return;
+        }
+
+        if self.drill.is_some() {
+            self.drill_move(mv);
+            return;
+        }
+
+        let Some(session) = &mut self.puzzle else {
+            return;
+        };
+        if session.is_solved() || !self.board.is_legal(mv) {
+            return;
+        }
+
+        match session.attempt(&self.board, mv) {
+            Attempt::Correct(reply) => {
+                self.play_move(mv);
+                self.play_move(reply);
+            },
+            Attempt::Solved => {
+                self.play_move(mv);
+                self.notice = Some("Solved!".to_owned());
+            },
+            Attempt::Wrong => {
+                let start = session.puzzle().start.clone();
+                self.load_position(start);
+                self.start_flash(mv);
+            },
+        }
+    }
+
+    /// Shows the last game of the watched PGN file if the file changed since
+    /// it was last read. A file caught halfway through a write doesn't parse
+    /// and is read again on the next check.
+    fn reload_watched_pgn(&mut self) {
+        let Some((path, shown)) = &self.watched_pgn else {
+            return;
+        };
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        if modified.is_some() && modified == *shown {
+            return;
+        }
+
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return;
+        };
+        let Some(Ok(game)) = pgn::split_games(&contents).last().map(|game| pgn::read_game(game)) else {
+            return;
+        };
+        if let Some((_, shown)) = &mut self.watched_pgn {
+            *shown = modified;
+        }
+        if game.moves == self.move_list && game.start.same_position(&self.start_position) {
+            return;
+        }
+
+        self.load_game(game);
+        if let Some(mv) = self.last_move {
+            self.start_slide(mv);
+        }
+    }
+
+    /// Slides the piece of `mv` from its origin, unless animations are off.
+    fn start_slide(&mut self, mv: Move) {
+        if self.settings.animations {
+            self.slide = Some((mv, Instant::now()));
+        }
+    }
+
+    /// Briefly marks a wrong move, unless animations are off.
+    fn start_flash(&mut self, mv: Move) {
+        if self.settings.animations {
+            self.flash = Some((mv, Instant::now()));
+        }
+    }
+
+    /// How long an animation lasts at the chosen speed.
+    fn animation_duration(&self, duration: Duration) -> Duration {
+        duration.div_f32(self.settings.animation_speed.max(0.1))
+    }
+
+    /// Checks a move played in an opening drill against the line.
+    fn drill_move(&mut self, mv: Move) {
+        let Some(drill) = &mut self.drill else {
+            return;
+        };
+        if drill.expected.is_some() || drill.is_complete() || !self.board.is_legal(mv) {
+            return;
+        }
+
+        match drill.attempt(mv) {
+            Attempt::Correct(reply) => {
+                self.play_move(mv);
+                self.play_move(reply);
+            },
+            Attempt::Solved => {
+                self.play_move(mv);
+            },
+            Attempt::Wrong => {
+                let expected = drill.expected.map_or(String::new(), |expected| san::to_san(&self.board, expected));
+                self.start_flash(mv);
+                self.notice = Some(format!("Expected {expected}"));
+            },
+        }
+
+        if self.drill.as_ref().is_some_and(Drill::is_complete) {
+            self.notice = Some("Line complete!".to_owned());
+        }
+    }
+
+    fn restart_drill(&mut self) {
+        let Some(drill) = &mut self.drill else {
+            return;
+        };
+
+        let first = drill.restart();
+        let side = drill.side;
+        self.load_position(Board::default());
+        if let Some(first) = first {
+            self.play_move(first);
+        }
+        self.flipped = side == cozy_chess::Color::Black;
+        self.state = State::Puzzle;
+        self.notice = None;
+    }
+
+    fn start_puzzle(&mut self) {
+        let Some(session) = &self.puzzle else {
+            return;
+        };
+
+        let start = session.puzzle().start.clone();
+        self.load_position(start);
+        self.flipped = self.board.side_to_move() == cozy_chess::Color::Black;
+        self.state = State::Puzzle;
+        self.notice = None;
+    }
+
+    fn start_match_game(&mut self) {
+        self.load_position(Board::default());
+        self.state = State::Waiting;
+
+        let opponent_ready = match &mut self.engine_match {
+            Some(engine_match) => engine_match.opponent.new_game(),
+            None => return,
+        };
+        let main_ready = match &mut self.engine {
+            Some(engine) => engine.new_game(),
+            None => Err(std::io::Error::other("no main engine")),
+        };
+
+        match opponent_ready.and(main_ready) {
+            Ok(()) => self.request_match_move(),
+            Err(err) => self.abort_match(err),
+        }
+    }
+
+    /// Asks whichever engine has the move to start thinking.
+    fn request_match_move(&mut self) {
+        let Some(engine_match) = &mut self.engine_match else {
+            return;
+        };
+
+        let white_to_move = self.board.side_to_move() == cozy_chess::Color::White;
+        let movetime = engine_match.movetime;
+        let engine = if white_to_move == engine_match.main_is_white {
+            self.engine.as_mut()
+        } else {
+            Some(&mut engine_match.opponent)
+        };
+
+        let started = match engine {
+            Some(engine) => engine.go(&self.start_position, &self.move_list, movetime),
+            None => Err(std::io::Error::other("no main engine")),
+        };
+        if let Err(err) = started {
+            self.abort_match(err);
+        }
+    }
+
+    fn poll_match_engine(&mut self) {
+        let Some(engine_match) = &mut self.engine_match else {
+            return;
+        };
+
+        let answer = self.engine.as_mut().and_then(Engine::poll_best_move)
+            .or_else(|| engine_match.opponent.poll_best_move());
+
+        match answer {
+            Some(Ok(mv)) => {
+                self.play_move(mv);
+                self.after_match_move();
+            },
+            Some(Err(err)) => self.abort_match(err),
+            None => {},
+        }
+    }
+
+    /// Stops the engine thinking about its reply in an engine game, so the
+    /// `bestmove` it owes isn't read against the next position it's sent.
+    fn stop_engine_reply(&mut self) {
+        if !self.vs_engine {
+            return;
+        }
+        if let Some(engine) = &mut self.engine {
+            if let Err(err) = engine.stop() {
+                self.notice = Some(format!("Engine error: {err}"));
+            }
+        }
+    }
+
+    /// Plays the engine's reply once it has one, when the engine is the opponent.
+    fn poll_opponent_engine(&mut self) {
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+
+        match engine.poll_best_move() {
+            // the reply is dropped if the game moved on while the engine was thinking
+            Some(Ok(mv)) if self.state == State::Waiting && self.board.side_to_move() == self.bot_color => {
+                self.state = State::Playing;
+                self.play_move(mv);
+                self.play_premove();
+            },
+            Some(Ok(_)) => {},
+            Some(Err(err)) => {
+                self.notice = Some(format!("Engine error: {err}"));
+                if self.state == State::Waiting {
+                    self.state = State::Playing;
+                    self.bot_stopped_at = Some(self.board.hash());
+                }
+            },
+            None => {},
+        }
+    }
+
+    fn after_match_move(&mut self) {
+        let hashes = position::position_hashes(&self.start_position, &self.move_list);
+        let result = GameEnd::detect(&self.board, &hashes).map(|end| end.result().to_owned())
+            .or((self.move_list.len() >= MATCH_MAX_PLIES).then(|| "1/2-1/2".to_owned()));
+
+        let Some(result) = result else {
+            self.request_match_move();
+            return;
+        };
+        let Some(engine_match) = &mut self.engine_match else {
+            return;
+        };
+
+        let main_name = self.engine.as_ref().map_or(String::new(), |engine| engine.name.clone());
+        let opponent_name = engine_match.opponent.name.clone();
+        let (white, black) = if engine_match.main_is_white {
+            (main_name, opponent_name)
+        } else {
+            (opponent_name, main_name)
+        };
+        let headers = [
+            ("Event", "Engine match".to_owned()),
+            ("Round", (engine_match.played + 1).to_string()),
+            ("White", white),
+            ("Black", black),
+        ];
+        let game = pgn::write_game(&headers, &self.start_position, self.settings.shredder_fen, &self.move_list, |_| None, result);
+        if let Err(err) = append_to_file(MATCH_PGN_PATH, &game) {
+            eprintln!("failed to record match game: {err}");
+        }
+
+        engine_match.record(result);
+        if engine_match.is_finished() {
+            self.notice = Some(format!("Match finished: {} (saved to {MATCH_PGN_PATH})", engine_match.score()));
+            self.engine_match = None;
+            self.state = State::Playing;
+        } else {
+            self.start_match_game();
+        }
+    }
+
+    fn abort_match(&mut self, err: std::io::Error) {
+        self.notice = Some(format!("Match aborted: {err}"));
+        self.stop_match();
+    }
+
+    fn stop_match(&mut self) {
+        // dropping the opponent shuts its process down
+        self.engine_match = None;
+        if let Some(engine) = &mut self.engine {
+            let _ = engine.new_game();
+        }
+        if self.state == State::Waiting {
+            self.state = State::Playing;
+        }
+    }
+
+    /// Launches the UCI engine at `path` in the background, delivering it
+    /// through [`Message::EngineStarted`]. `remember` makes it the saved engine.
+    fn start_engine(&mut self, path: PathBuf, remember: bool) -> Task<Message> {
+        self.notice = Some(format!("Starting engine {}...", path.display()));
+        Task::perform(
+            engine::start_in_background(path.clone()),
+            move |result| Message::EngineStarted(path, remember, result),
+        )
+    }
+
+    /// Takes over an engine that completed the `uci` handshake and restores
+    /// the option values saved for it.
+    fn connect_engine(&mut self, mut engine: Engine) {
+        let key = engine.path.display().to_string();
+        for (_, name, value) in self.settings.engine_options.iter().filter(|(p, _, _)| *p == key) {
+            // buttons saved by older versions would fire on every start
+            if engine.is_button(name) {
+                continue;
+            }
+            if let Err(err) = engine.set_option(name, value) {
+                eprintln!("failed to restore engine option {name}: {err}");
+            }
+        }
+        self.notice = Some(format!("Engine ready: {}", engine.name));
+        self.engine = Some(engine);
+
+        // a reply the old engine owed is never coming
+        if self.vs_engine && self.state == State::Waiting {
+            self.state = State::Playing;
+        }
+    }
+
+    /// Lets every engine process quit before the app exits, which doesn't
+    /// wait for destructors to run.
+    fn shut_down_engines(&mut self) {
+        if let Some(mut engine_match) = self.engine_match.take() {
+            engine_match.opponent.shutdown();
+        }
+        if let Some(mut engine) = self.engine.take() {
+            engine.shutdown();
+        }
+    }
+
+    /// Starts the saved engine again when it was shut down after its last
+    /// game, running `message` once it's ready. `None` when there's nothing
+    /// to start.
+    fn restart_engine_for(&mut self, message: Message) -> Option<Task<Message>> {
+        if self.engine.is_some() {
+            return None;
+        }
+        let path = self.settings.engine_path.clone()?;
+        if self.waiting_for_engine.is_some() {
+            return Some(Task::none());
+        }
+        self.waiting_for_engine = Some(message);
+        Some(self.start_engine(path, false))
+    }
+
+    /// Shuts the engine down when neither game nor match needs it. The next
+    /// engine game starts it again from the saved path.
+    fn release_engine(&mut self) {
+        let in_use = self.vs_engine
+            || self.engine_match.is_some()
+            || self.saved_game.as_ref().is_some_and(|saved| saved.vs_engine);
+        if in_use {
+            return;
+        }
+        if let Some(mut engine) = self.engine.take() {
+            engine.shutdown();
+        }
+    }
+
+    /// Drops the engine, handing its games over to the built-in bot.
+    fn disconnect_engine(&mut self) {
+        if self.engine_match.is_some() {
+            self.stop_match();
+        }
+        self.engine = None;
+        if self.vs_engine {
+            self.vs_engine = false;
+            if self.state == State::Waiting {
+                self.state = State::Playing;
+            }
+        }
+    }
+
+    /// Loads piece images from `assets_dir`, noting any that are missing.
+    fn set_assets_dir(&mut self, assets_dir: PathBuf) {
+        self.missing_assets = render::missing_assets(&assets_dir);
+        self.tinted_pieces.clear();
+        for color in cozy_chess::Color::ALL {
+            for piece in Piece::ALL {
+                if render::piece_asset_path(&assets_dir, color, piece).is_file() {
+                    continue;
+                }
+                if let Ok((img, true)) = render::load_piece_image(&assets_dir, color, piece) {
+                    let handle = image::Handle::from_rgba(img.width(), img.height(), img.into_raw());
+                    self.tinted_pieces.push((color, piece, handle));
+                }
+            }
+        }
+
+        if !self.tinted_pieces.is_empty() && !self.tint_warned {
+            eprintln!("piece set has no image for some colors, tinting its neutral images instead");
+            self.tint_warned = true;
+        }
+        self.assets_dir = assets_dir;
+    }
+
+    /// Image drawn for a piece, the tinted fallback included.
+    fn piece_image(&self, color: cozy_chess::Color, piece: Piece) -> image::Handle {
+        self.tinted_pieces.iter()
+            .find(|(c, p, _)| *c == color && *p == piece)
+            .map(|(_, _, handle)| handle.clone())
+            .unwrap_or_else(|| image::Handle::from_path(render::piece_asset_path(&self.assets_dir, color, piece)))
+    }
+
+    /// Restarts the bot's random choices from `seed`, so its games can be
+    /// reproduced.
+    fn seed_bot(&mut self, seed: u64) {
+        self.bot.seed = seed;
+        self.bot_rng = self.bot.rng();
+    }
+
+    /// Loads a reproducible random position, reporting the seed used.
+    fn load_random_position(&mut self, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        self.load_position(position::random_position(&mut rng, RANDOM_POSITION_PLIES));
+        self.notice = Some(format!("Random position, seed {seed}"));
+    }
+
+    /// Replaces the game with a fresh one starting from `board`.
+    fn load_position(&mut self, board: Board) {
+        if matches!(self.state, State::GameOver(_)) {
+            self.state = State::Playing;
+        }
+        self.game_end = None;
+        self.draw_offered_by = None;
+        self.start_position = board.clone();
+        self.board = board;
+        self.move_list.clear();
+        self.move_clocks.clear();
+        self.annotations.clear();
+        self.eval_graph = None;
+        self.move_reviews = None;
+        self.dirty = false;
+        if self.clock.is_some() {
+            self.clock = Some(self.new_clock());
+        }
+        self.variation = None;
+        self.last_move = None;
+        self.selected = None;
+        self.premove = None;
+        self.promotion_square = None;
+        self.saved_game = None;
+        self.blunder = None;
+        // only a game started from the dialog is played seriously
+        self.game_mode = GameMode::Casual;
+    }
+
+    /// A clock set to the time control of the last New Game dialog, or the
+    /// default one when that game was played without a clock.
+    fn new_clock(&self) -> Clock {
+        let (base, increment) = self.settings.game_setup.clock.unwrap_or((clock::BASE_TIME, clock::INCREMENT));
+        Clock::new(base, increment)
+    }
+
+    /// Starts a game from the initial position as chosen in the New Game
+    /// dialog, leaving puzzles, drills and matches behind.
+    fn start_game(&mut self, setup: GameSetup) {
+        self.settings.game_setup = setup.clone();
+        self.save_settings();
+
+        if self.engine_match.is_some() {
+            self.stop_match();
+        }
+        self.puzzle = None;
+        self.drill = None;
+        let start = fen::parse_fen(&setup.start_fen).unwrap_or_default();
+        let black_starts = start.side_to_move() == cozy_chess::Color::Black;
+        self.load_position(start);
+        self.clock = setup.clock.map(|(base, increment)| Clock::new(base, increment));
+        self.vs_bot = setup.opponent != Opponent::Human;
+        self.vs_engine = setup.opponent == Opponent::Engine;
+        self.bot_color = !setup.human_color;
+        self.game_mode = setup.mode;
+        self.bot_stopped_at = None;
+        if self.vs_bot {
+            self.orient_for_bot_game();
+        } else if self.settings.orientation_lock.is_none() {
+            // the side to move sits at the bottom, as in a study position
+            self.flipped = black_starts;
+        }
+        if self.vs_engine {
+            if let Some(engine) = &mut self.engine {
+                let _ = engine.new_game();
+            }
+        } else {
+            self.release_engine();
+        }
+        self.notice = None;
+    }
+
+    /// What's needed to pick up the current game on the next launch.
+    fn session(&self) -> Session {
+        // the game played on from a position is only a side trip
+        if let Some(saved) = &self.saved_game {
+            return Session {
+                start: saved.start.clone(),
+                moves: saved.moves.clone(),
+                variation: saved.variation.as_ref().map(|variation| (variation.branch_ply, variation.moves.clone())),
+                game_end: saved.game_end.clone(),
+                flipped: saved.flipped,
+                vs_bot: saved.vs_bot,
+                bot_color: saved.bot_color,
+            };
+        }
+
+        Session {
+            start: self.start_position.clone(),
+            moves: self.move_list.clone(),
+            variation: self.variation.as_ref().map(|variation| (variation.branch_ply, variation.moves.clone())),
+            game_end: self.game_end.clone(),
+            flipped: self.flipped,
+            vs_bot: self.vs_bot,
+            bot_color: self.bot_color,
+        }
+    }
+
+    fn restore_session(&mut self, session: Session) {
+        self.load_position(session.start);
+        self.move_clocks = vec![None; session.moves.len()];
+        self.move_list = session.moves;
+        self.variation = session.variation.map(|(branch_ply, moves)| Variation { branch_ply, moves });
+        self.game_end = session.game_end;
+        self.flipped = session.flipped;
+        self.vs_bot = session.vs_bot;
+        self.bot_color = session.bot_color;
+        self.replay();
+    }
+
+    /// Replaces the game with an imported one, keeping its recorded outcome.
+    fn load_game(&mut self, game: pgn::ImportedGame) {
+        self.load_position(game.start);
+        self.move_clocks = vec![None; game.moves.len()];
+        self.move_list = game.moves;
+        for (ply, comment) in &game.comments {
+            let annotations = Annotations::parse(comment);
+            if !annotations.is_empty() {
+                self.annotations.entry(*ply).or_default().extend(annotations);
+            }
+        }
+        self.replay();
+        self.notice = None;
+
+        // the final position tells how most games ended, the tag tells the rest
+        let hashes = position::position_hashes(&self.start_position, &self.move_list);
+        let end = GameEnd::detect(&self.board, &hashes)
+            .or_else(|| (game.result != "*").then(|| GameEnd::Recorded(game.result.clone())));
+        if let Some(end) = end {
+            self.end_game(end);
+        }
+    }
+
+    /// Moves leading to the shown position, through the variation if one is explored.
+    fn current_line(&self) -> Vec<Move> {
+        match &self.variation {
+            Some(variation) => self.move_list[..variation.branch_ply].iter().chain(&variation.moves).copied().collect(),
+            None => self.move_list.clone(),
+        }
+    }
+
+    /// Rebuilds the board from the game history and the variation being explored.
+    fn replay(&mut self) {
+        self.premove = None;
+        let (game_moves, variation_moves) = match &self.variation {
+            Some(variation) => (&self.move_list[..variation.branch_ply], variation.moves.as_slice()),
+            None => (self.move_list.as_slice(), &[][..]),
+        };
+
+        let mut board = self.start_position.clone();
+        let mut last_move = None;
+        for &mv in game_moves.iter().chain(variation_moves) {
+            last_move = Some(position::to_standard_castling(&board, mv));
+            board.play_unchecked(mv);
+        }
+
+        self.board = board;
+        self.last_move = last_move;
+        self.move_clocks.truncate(self.move_list.len());
+        let plies = self.move_list.len();
+        self.annotations.retain(|&ply, _| ply <= plies);
+        if self.eval_graph.as_ref().is_some_and(|values| values.len() != self.move_list.len() + 1) {
+            self.eval_graph = None;
+        }
+        if self.move_reviews.as_ref().is_some_and(|reviews| reviews.len() != self.move_list.len()) {
+            self.move_reviews = None;
+        }
+        self.selected = None;
+        self.promotion_square = None;
+        self.blunder = None;
+
+        // a finished game can still be analyzed, and a pending bot search
+        // belongs to the game rather than the variation
+        if self.variation.is_some() && matches!(self.state, State::GameOver(_) | State::Waiting) {
+            self.state = State::Playing;
+        } else if let Some(end) = self.game_end.as_ref().filter(|_| self.variation.is_none()) {
+            self.state = State::GameOver(end.clone());
+        }
+    }
+
+    /// Playing on against the bot is offered from any position of the game
+    /// under review that isn't already over.
+    fn can_play_on(&self) -> bool {
+        self.saved_game.is_none()
+            && self.can_review()
+            && matches!(self.state, State::Playing | State::GameOver(_))
+            && self.board.status() == GameStatus::Ongoing
+    }
+
+    /// Sets the game aside and starts one against the bot from the position
+    /// on the board, with the human moving first.
+    fn play_on(&mut self) {
+        let board = self.board.clone();
+        let saved = SavedGame {
+            start: self.start_position.clone(),
+            moves: std::mem::take(&mut self.move_list),
+            move_clocks: std::mem::take(&mut self.move_clocks),
+            annotations: std::mem::take(&mut self.annotations),
+            variation: self.variation.take(),
+            game_end: self.game_end.take(),
+            clock: self.clock.take(),
+            dirty: self.dirty,
+            flipped: self.flipped,
+            vs_bot: self.vs_bot,
+            vs_engine: self.vs_engine,
+            bot_color: self.bot_color,
+        };
+
+        // the board keeps its castling rights and en passant square
+        self.load_position(board);
+        self.saved_game = Some(saved);
+        self.vs_bot = true;
+        self.vs_engine = self.settings.game_setup.opponent == Opponent::Engine && self.engine.is_some();
+        self.bot_color = !self.board.side_to_move();
+        self.bot_stopped_at = None;
+        self.orient_for_bot_game();
+        if self.vs_engine {
+            if let Some(engine) = &mut self.engine {
+                let _ = engine.new_game();
+            }
+        } else {
+            self.release_engine();
+        }
+        self.notice = None;
+    }
+
+    /// Drops the game played on and brings back the one it started from.
+    fn return_to_game(&mut self) {
+        let Some(saved) = self.saved_game.take() else {
+            return;
+        };
+
+        self.stop_engine_reply();
+        self.load_position(saved.start);
+        self.move_list = saved.moves;
+        self.move_clocks = saved.move_clocks;
+        self.annotations = saved.annotations;
+        self.variation = saved.variation;
+        self.game_end = saved.game_end;
+        self.clock = saved.clock;
+        self.dirty = saved.dirty;
+        self.flipped = saved.flipped;
+        self.vs_bot = saved.vs_bot;
+        self.vs_engine = saved.vs_engine;
+        self.bot_color = saved.bot_color;
+        self.bot_stopped_at = None;
+        // a reply still being searched belonged to the game played on
+        if self.state == State::Waiting {
+            self.state = State::Playing;
+        }
+        self.release_engine();
+        self.replay();
+    }
+
+    /// Taking moves back against the bot, which is only possible on the
+    /// human's turn so no search is left running for a stale position.
+    fn can_take_back(&self) -> bool {
+        self.vs_bot
+            && self.state == State::Playing
+            && self.variation.is_none()
+            && !self.move_list.is_empty()
+    }
+
+    /// Runs the clock of the side to move while the game is in progress,
+    /// ending the game when a flag falls.
+    fn tick_clock(&mut self, now: Instant) {
+        let running = matches!(self.state, State::Playing | State::Waiting)
+            && self.variation.is_none()
+            && self.puzzle.is_none()
+            && self.engine_match.is_none();
+        let Some(clock) = &mut self.clock else {
+            return;
+        };
+        if !running {
+            clock.pause();
+            return;
+        }
+
+        let to_move = self.board.side_to_move();
+        let flagged = clock.tick(now, to_move);
+        let warn = cozy_chess::Color::ALL.map(|color| clock.crossed_low_time(color)).contains(&true);
+        if warn && self.settings.sound_enabled {
+            self.audio.play(SoundKind::LowTime);
+        }
+
+        if flagged {
+            self.end_game(GameEnd::Timeout(to_move));
+            self.auto_save();
+        }
+    }
+
+    /// The selected piece's legal move onto the hovered tile, if there is one.
+    fn hovered_move(&self) -> Option<Move> {
+        let from = self.selected?;
+        let (x, y) = self.hovered_tile?;
+        self.legal_move(from, self.square_at(x, y))
+    }
+
+    /// A legal move from `from` to `to`, the first one for promotions.
+    fn legal_move(&self, from: Square, to: Square) -> Option<Move> {
+        let mut found = None;
+        self.board.generate_moves_for(from.bitboard(), |moves| {
+            found = moves.into_iter().find(|mv| mv.to == to);
+            found.is_some()
+        });
+        found
+    }
+
+    /// Ply of the game shown on the board, or `None` inside a variation.
+    fn current_ply(&self) -> Option<usize> {
+        match &self.variation {
+            Some(variation) if !variation.moves.is_empty() => None,
+            Some(variation) => Some(variation.branch_ply),
+            None => Some(self.move_list.len()),
+        }
+    }
+
+    /// Navigation keys stay inert during play, where they'd be confusing,
+    /// and only work after the game or while stepping through it. A pending
+    /// promotion or bot reply keeps them inert either way.
+    fn can_navigate_by_keyboard(&self) -> bool {
+        let reviewing = match self.state {
+            State::GameOver(_) => true,
+            State::Playing => self.variation.is_some(),
+            State::Waiting | State::Promoting | State::Puzzle => false,
+        };
+        self.can_review() && reviewing
+    }
+
+    /// Ply before White's move `number`, clamped to the game.
+    fn ply_of_move_number(&self, number: usize) -> usize {
+        let first = self.start_position.fullmove_number() as usize;
+        let black_starts = self.start_position.side_to_move() == cozy_chess::Color::Black;
+        let ply = (number.saturating_sub(first) * 2).saturating_sub(black_starts as usize);
+        ply.min(self.move_list.len())
+    }
+
+    /// Stepping through the game is left to the user outside of puzzles and matches.
+    fn can_review(&self) -> bool {
+        self.puzzle.is_none() && self.drill.is_none() && self.engine_match.is_none()
+    }
+
+    /// Two people sharing the board, as opposed to playing the bot, puzzles
+    /// or watching engines.
+    fn is_hotseat(&self) -> bool {
+        !self.vs_bot && self.puzzle.is_none() && self.drill.is_none() && self.engine_match.is_none()
+    }
+
+    /// Whether a move by `mover` should be heard. Only moves the user plays
+    /// against the bot count as their own; hotseat moves always sound.
+    fn wants_move_sound(&self, mover: cozy_chess::Color) -> bool {
+        match self.settings.move_sounds {
+            MoveSounds::Always => true,
+            MoveSounds::OpponentOnly => !(self.vs_bot && mover != self.bot_color),
+            MoveSounds::Never => false,
+        }
+    }
+
+    fn end_game(&mut self, end: GameEnd) {
+        self.state = State::GameOver(end.clone());
+        self.game_end = Some(end);
+        self.draw_offered_by = None;
+        self.selected = None;
+    }
+
+    /// Plays `mv` if it is legal, recording it in the game history.
+    fn play_move(&mut self, mv: Move) -> bool {
+        let shown = position::to_standard_castling(&self.board, mv);
+        let captures = position::is_capture(&self.board, mv);
+        let mover = self.board.side_to_move();
+        if self.board.try_play(mv).is_err() {
+            return false;
+        }
+
+        // an offer lapses once the opponent answers it with a move
+        if self.draw_offered_by.is_some_and(|offerer| offerer != mover) {
+            self.draw_offered_by = None;
+        }
+
+        if self.settings.sound_enabled && self.wants_move_sound(mover) {
+            let kind = if !self.board.checkers().is_empty() {
+                SoundKind::Check
+            } else if captures {
+                SoundKind::Capture
+            } else {
+                SoundKind::Move
+            };
+            self.audio.play(kind);
+        }
+
+        self.last_move = Some(shown);
+        match &mut self.variation {
+            Some(variation) => variation.moves.push(mv),
+            None => {
+                self.move_list.push(mv);
+                self.eval_graph = None;
+                self.move_reviews = None;
+                self.move_clocks.push(self.clock.as_mut().map(|clock| clock.press(mover)));
+                self.dirty |= self.puzzle.is_none() && self.drill.is_none() && self.engine_match.is_none();
+            },
+        }
+
+        // matches, puzzles and drills decide for themselves when they're over
+        if self.variation.is_none() && (self.is_hotseat() || self.vs_bot) {
+            let hashes = position::position_hashes(&self.start_position, &self.move_list);
+            if let Some(end) = GameEnd::detect(&self.board, &hashes) {
+                self.end_game(end);
+                self.auto_save();
+            }
+        }
+
+        // hotseat games turn the board towards whoever has to move
+        if self.is_hotseat() {
+            self.flipped = self.board.side_to_move() == cozy_chess::Color::Black;
+        }
+        true
+    }
+
+    /// Selects one of our pieces, or queues a move for it, while the bot is
+    /// thinking. Only the piece's own movement is checked, the rest waits
+    /// until the bot has replied.
+    fn queue_premove(&mut self, point: Point) {
+        let square = self.square_from_point(point);
+        let ours = self.board.colors(!self.bot_color);
+
+        match (self.selected.filter(|&from| ours.has(from)), square) {
+            (Some(from), Some(to)) if self.vs_bot && position::pseudo_legal_destinations(&self.board, from).has(to) => {
+                // premoved pawns always promote to a queen
+                let promotes = self.board.pieces(Piece::Pawn).has(from)
+                    && Rank::Eighth.relative_to(!self.bot_color).bitboard().has(to);
+                self.premove = Some(Move { from, to, promotion: promotes.then_some(Piece::Queen) });
+                self.selected = None;
+            },
+            _ => {
+                self.premove = None;
+                self.selected = square.filter(|&square| ours.has(square));
+            },
+        }
+    }
+
+    /// Plays the queued premove now that the bot has replied. The reply may
+    /// have pinned the piece, blocked its path or put us in check, so the
+    /// move is checked against the real board and dropped if it's illegal.
+    fn play_premove(&mut self) {
+        let Some(premove) = self.premove.take() else {
+            return;
+        };
+        if self.state != State::Playing {
+            return;
+        }
+
+        if let Some(premove) = position::validate_premove(&self.board, premove) {
+            self.play_move(premove);
+        } else {
+            self.notice = Some("Premove cancelled".to_owned());
+        }
+    }
+
+    /// Starts searching for the bot's reply when it is the bot's turn.
+    fn start_bot_move(&mut self) -> Task<Message> {
+        let bots_turn = self.vs_bot
+            && self.state == State::Playing
+            && self.variation.is_none()
+            && self.board.side_to_move() == self.bot_color
+            && self.board.status() == GameStatus::Ongoing
+            && self.bot_stopped_at != Some(self.board.hash());
+        if !bots_turn {
+            return Task::none();
+        }
+
+        self.state = State::Waiting;
+        let board = self.board.clone();
+
+        if self.vs_engine {
+            let movetime = Duration::from_millis(self.settings.search_time_ms as u64);
+            let started = match &mut self.engine {
+                Some(engine) => engine.go(&self.start_position, &self.move_list, movetime),
+                None => Err(std::io::Error::other("no engine loaded")),
+            };
+            if let Err(err) = started {
+                self.notice = Some(format!("Engine error: {err}"));
+                self.state = State::Playing;
+                self.bot_stopped_at = Some(self.board.hash());
+            }
+            return Task::none();
+        }
+
+        let book_move = self.settings.use_opening_book
+            .then(|| self.bot.book_move(&board, &mut self.bot_rng))
+            .flatten();
+        if let Some(mv) = book_move {
+            return Task::done(Message::BotMoved(board, SearchResult {
+                best_move: Some(mv),
+                score: eval::evaluate(&self.board),
+                depth: 0,
+                nodes: 0,
+                pv: vec![mv],
+            }));
+        }
+
+        let time_limit = Duration::from_millis(self.settings.search_time_ms as u64);
+        Task::perform(
+            search::search_in_background(board.clone(), time_limit, self.bot.depth, self.search_stop.clone()),
+            move |result| Message::BotMoved(board, result),
+        )
+    }
+
+    /// Hands the move to the other side without playing anything.
+    fn pass_turn(&mut self) {
+        if !self.board.checkers().is_empty() {
+            self.notice = Some("Can't pass while in check".to_owned());
+            return;
+        }
+
+        match position::flip_side_to_move(&self.board) {
+            Ok(board) => {
+                // a null move can't be replayed, so the game restarts from here
+                self.load_position(board);
+                self.notice = None;
+            },
+            Err(err) => self.notice = Some(format!("Can't pass: {err:?}")),
+        }
+    }
+
+    /// Monospace summary of the position, one FEN field per line.
+    fn position_info(&self) -> Element<Message> {
+        let fen = self.board.to_string();
+        let fields: Vec<&str> = fen.split(' ').collect();
+
+        text(format!(
+            "fen:        {}\n\
+             to move:    {:?}\n\
+             castling:   {}\n\
+             en passant: {}\n\
+             halfmove:   {}\n\
+             fullmove:   {}\n\
+             selected:   {}\n\
+             status:     {:?}\n\
+             state:      {:?}",
+            fen,
+            self.board.side_to_move(),
+            position::castling_rights_string(&self.board),
+            fields[3],
+            self.board.halfmove_clock(),
+            self.board.fullmove_number(),
+            self.selected.map_or("-".to_owned(), |square| square.to_string()),
+            self.board.status(),
+            self.state,
+        ))
+        .font(Font::MONOSPACE)
+        .size(16)
+        .into()
+    }
+
+    fn game_controls(&self) -> Element<Message> {
+        let playing = self.is_hotseat() && self.state == State::Playing;
+        let clock = row![
+            checkbox(
+                {
+                    let (base, increment) = self.settings.game_setup.clock.unwrap_or((clock::BASE_TIME, clock::INCREMENT));
+                    format!("Clock {}+{}", base.as_secs() / 60, increment.as_secs())
+                },
+                self.clock.is_some(),
+            ).on_toggle(Message::ClockToggled),
+            checkbox("Clock annotations in PGN", self.settings.pgn_clock_annotations)
+                .on_toggle(Message::ClockAnnotationsToggled),
+        ].spacing(10);
+        let auto_save = row![
+            checkbox("Auto-save finished games", self.settings.auto_save_dir.is_some())
+                .on_toggle(Message::AutoSaveToggled),
+        ].push_maybe(self.settings.auto_save_dir.as_ref().map(|dir| text(dir.display().to_string()))).spacing(10);
+        let times = self.clock.as_ref().map(|clock| {
+            let time = |color: cozy_chess::Color| {
+                let remaining = clock.remaining(color);
+                let low = remaining < clock::LOW_TIME;
+                text(format!("{color:?} {}", clock::format_time(remaining)))
+                    .font(Font::MONOSPACE)
+                    .size(24)
+                    .color_maybe(low.then_some(Color::from_rgb(0.9, 0.1, 0.1)))
+            };
+            row![time(cozy_chess::Color::White), time(cozy_chess::Color::Black)].spacing(30)
+        });
+        let buttons = row![
+            button("New game").on_press(Message::NewGame),
+            button("Load PGN").on_press(Message::LoadPgn),
+            button("Watch PGN").on_press(Message::WatchPgn),
+            button("Save PGN").on_press(Message::SavePgn),
+            button("Copy moves").on_press(Message::CopyMoves),
+            button("Resign").on_press_maybe(playing.then_some(Message::Resign)),
+            button("Offer draw").on_press_maybe((playing && self.draw_offered_by.is_none()).then_some(Message::OfferDraw)),
+        ].spacing(10);
+
+        let offer = self.draw_offered_by.map(|offerer| row![
+            text(format!("{offerer:?} offers a draw")),
+            button("Accept").on_press(Message::AcceptDraw),
+            button("Decline").on_press(Message::DeclineDraw),
+        ].spacing(10));
+
+        let export = checkbox("Shredder FEN for copies and PGN", self.settings.shredder_fen)
+            .on_toggle(Message::ShredderFenToggled);
+
+        let collection = self.pgn_collection.as_ref().map(|collection| row![
+            pick_list(collection.headers.as_slice(), self.collection_game.clone(), Message::CollectionGameSelected)
+                .placeholder(format!("{} games", collection.games.len())),
+            button("Close").on_press(Message::CollectionClosed),
+        ].spacing(10));
+
+        let preset = row![
+            text_input("Name this position", &self.preset_name_input)
+                .on_input(Message::PresetNameChanged)
+                .on_submit(Message::SavePreset)
+                .width(200),
+            button("Save as preset").on_press(Message::SavePreset),
+        ].spacing(10);
+
+        let watching = self.watched_pgn.as_ref().map(|(path, _)| row![
+            text(format!("Following {}", path.display())),
+            button("Stop").on_press(Message::StopWatching),
+        ].spacing(10));
+
+        column![buttons, clock, auto_save, export, preset].push_maybe(watching).push_maybe(collection).push_maybe(times).push_maybe(offer).into()
+    }
+
+    /// Game navigation, plus the variation being explored when there is one.
+    fn history_controls(&self) -> Element<Message> {
+        let exploring = self.variation.as_ref().is_some_and(|variation| !variation.moves.is_empty());
+        let reviewing = self.variation.as_ref().is_some_and(|variation| variation.moves.is_empty());
+        let can_step_back = self.can_review() && !exploring && match &self.variation {
+            Some(variation) => variation.branch_ply > 0,
+            None => !self.move_list.is_empty(),
+        };
+        let can_undo = self.variation.is_some() || (self.is_hotseat() && self.state == State::Playing);
+
+        let buttons = row![
+            button("<").on_press_maybe(can_step_back.then_some(Message::StepBack)),
+            button(">").on_press_maybe(reviewing.then_some(Message::StepForward)),
+            button("Undo").on_press_maybe(can_undo.then_some(Message::Undo)),
+            button("Takeback").on_press_maybe(self.can_take_back().then_some(Message::Takeback)),
+            text(match self.current_ply() {
+                Some(ply) => format!("ply {ply}/{}", self.move_list.len()),
+                None => "analysis".to_owned(),
+            }),
+        ]
+        .push(button("Play on vs bot").on_press_maybe(self.can_play_on().then_some(Message::PlayOnFromHere)))
+        .push_maybe(self.saved_game.is_some().then(|| button("Back to game").on_press(Message::BackToGame)))
+        .push_maybe((!self.typed_move_number.is_empty()).then(|| text(format!("go to move {}", self.typed_move_number))))
+        .spacing(10);
+
+        let Some(variation) = &self.variation else {
+            return buttons.into();
+        };
+
+        let mut branch = self.start_position.clone();
+        for &mv in &self.move_list[..variation.branch_ply] {
+            branch.play_unchecked(mv);
+        }
+
+        column![
+            buttons,
+            text(format!(
+                "analysis from ply {}: {}",
+                variation.branch_ply,
+                san::move_list_text(&branch, &variation.moves),
+            )).color(Color::from_rgb(0.3, 0.5, 0.9)).width(400),
+            button("Return to game").on_press(Message::ReturnToGame),
+        ].spacing(5).into()
+    }
+
+    /// The game's moves, which once annotated carry their glyphs and jump to
+    /// the position they were played from when clicked.
+    fn move_list_panel(&self) -> Element<Message> {
+        let Some(reviews) = &self.move_reviews else {
+            let annotate = button(if self.annotating { "Annotating..." } else { "Annotate" })
+                .on_press_maybe((self.game_end.is_some() && !self.annotating).then_some(Message::AnnotateGame));
+            return column![
+                text(san::move_list_text(&self.start_position, &self.move_list)).width(400),
+                annotate,
+            ].spacing(5).into();
+        };
+
+        let mut board = self.start_position.clone();
+        let mut moves = row![].spacing(2);
+        for (ply, (&mv, review)) in self.move_list.iter().zip(reviews).enumerate() {
+            let number = match board.side_to_move() {
+                cozy_chess::Color::White => format!("{}. ", board.fullmove_number()),
+                cozy_chess::Color::Black if ply == 0 => format!("{}... ", board.fullmove_number()),
+                cozy_chess::Color::Black => String::new(),
+            };
+            let label = format!("{number}{}{}", san::to_san(&board, mv), review.judgement.glyph());
+            board.play_unchecked(mv);
+
+            let color = match review.judgement {
+                Judgement::Good => None,
+                Judgement::Inaccuracy => Some(Color::from_rgb(0.8, 0.6, 0.0)),
+                Judgement::Mistake => Some(Color::from_rgb(0.9, 0.4, 0.0)),
+                Judgement::Blunder => Some(Color::from_rgb(0.9, 0.1, 0.1)),
+            };
+            moves = moves.push(button(text(label).color_maybe(color))
+                .style(button::text)
+                .padding(2)
+                .on_press(Message::JumpToPly(ply)));
+        }
+
+        moves.wrap().into()
+    }
+
+    /// The evaluation graph once computed, or the button computing it.
+    fn eval_graph_panel(&self) -> Element<Message> {
+        let Some(values) = &self.eval_graph else {
+            return button(if self.computing_eval_graph { "Evaluating..." } else { "Evaluation graph" })
+                .on_press_maybe((!self.move_list.is_empty() && !self.computing_eval_graph).then_some(Message::ComputeEvalGraph))
+                .into();
+        };
 
-fn index_to_coord(index: usize) -> (usize, usize) {
-    let x = index % 8;
-    let y = 7 - (index / 8);
-    (x, y)
-}
+        // a variation is marked where it leaves the game
+        let current = self.variation.as_ref().map_or(self.move_list.len(), |variation| variation.branch_ply);
+        Canvas::new(EvalGraph { values, current }).width(300).height(100).into()
+    }
 
-#[derive(Debug, PartialEq)]
-enum State {
-    Playing,
-    Waiting,
-    Promoting,
-}
+    /// Controls for every option the engine advertises, hidden when there are none.
+    fn engine_options_panel(&self) -> Option<Element<Message>> {
+        let engine = self.engine.as_ref().filter(|engine| !engine.options.is_empty())?;
 
-struct VisualBoard {
-    tile_size: f32,
-    dark_color: Color,
-    light_color: Color,
-    cache: Cache,
-    board: Board,
-    selected: Option<Square>,
-    promotion_square: Option<Square>,
-    state: State,
-    hovered_tile: Option<(usize, usize)>,
-}
+        let mut panel = column![
+            button(if self.show_engine_options { "Hide engine options" } else { "Engine options" })
+                .on_press(Message::EngineOptionsToggled),
+        ].spacing(5);
 
-#[derive(Debug, Clone, Copy)]
-enum Message {
-    Clicked(Point),
-    CursorMoved(Point),
-}
+        if !self.show_engine_options {
+            return Some(panel.into());
+        }
 
-impl VisualBoard {
-    fn update(&mut self, message: Message) {
-        self.cache.clear();
+        for option in &engine.options {
+            let name = option.name.clone();
+            let control: Element<Message> = match &option.kind {
+                OptionKind::Spin { min, max } => {
+                    let value = option.value.parse().unwrap_or(*min);
+                    row![
+                        text(format!("{}: {value}", option.name)),
+                        slider(*min..=*max, value, move |value| Message::EngineOptionChanged(name.clone(), value.to_string())).width(200),
+                    ].spacing(10).into()
+                },
+                OptionKind::Check => checkbox(option.name.clone(), option.value == "true")
+                    .on_toggle(move |value| Message::EngineOptionChanged(name.clone(), value.to_string()))
+                    .into(),
+                OptionKind::Combo { vars } => row![
+                    text(option.name.clone()),
+                    pick_list(vars.clone(), Some(option.value.clone()), move |value| Message::EngineOptionChanged(name.clone(), value)),
+                ].spacing(10).into(),
+                OptionKind::Button => button(text(option.name.clone()))
+                    .on_press(Message::EngineOptionChanged(name, String::new()))
+                    .into(),
+                OptionKind::String => row![
+                    text(option.name.clone()),
+                    text_input("", &option.value).on_input(move |value| Message::EngineOptionChanged(name.clone(), value)),
+                ].spacing(10).into(),
+            };
+            panel = panel.push(control);
+        }
 
-        match message {
-            Message::Clicked(point) => {
-                match self.state {
-                    State::Playing => {
-                        if let (Some(selected_square), Some(new_square)) = (self.selected, self.square_from_point(point)) {
-                             // check if move would allow promotion
-                            if let Some(piece) = self.board.piece_on(selected_square) {
-                                if piece == Piece::Pawn{
-                                    // piece is pawn so check its valid moves
-                                    let mut is_promotion_move = false;
-                                    self.board.generate_moves_for(selected_square.bitboard(), |moves| {
-                                        for mv in moves {
-                                            if mv.to == new_square && mv.promotion.is_some() {
-                                                // this is promotion move
-                                                is_promotion_move = true;
-                                                return true;
-                                            }
-                                        }
-                                        false
-                                    });
-
-                                    if is_promotion_move {
-                                        self.promotion_square = Some(new_square);
-                                        self.state = State::Promoting;
-                                        return;
-                                    }
-                                }
-                            }
-                           
-                            // if Rank::First.bitboard().has(new_square) || Rank::Eighth.bitboard().has(new_square) 
-                            // trying to move selected square to new point
-                            let _ = self.board.try_play(Move {
-                                from: selected_square,
-                                to: new_square,
-                                promotion: None,
-                            });
-                        };
-                        self.selected = self.square_from_point(point);
-                    },
-                    State::Waiting => {},
-                    State::Promoting => {
-                        if let Some((x, y)) = self.hovered_tile {
-                            if (2..=5).contains(&x) && y == 4 {
-                                // selected this one
-                                // determine what piece
-                                let piece = match x {
-                                    2 => Piece::Rook,
-                                    3 => Piece::Knight,
-                                    4 => Piece::Bishop,
-                                    5 => Piece::Queen,
-                                    _ => panic!("???")
-                                };
-
-                                let _ = self.board.try_play(Move {
-                                    from: self.selected.unwrap(),
-                                    to: self.promotion_square.unwrap(),
-                                    promotion: Some(piece),
-                                });
-
-                                self.state = State::Playing;
-                                self.selected = self.square_from_point(point);
-                                self.promotion_square = None;
-                            }
-                        }
-                    },
-                }
-            },
-            Message::CursorMoved(point) => {
-                let (square_x, square_y) = self.canvas_coord_to_square_coord(point);
-                self.hovered_tile = if square_x >= 8.0 || square_x < 0.0 || square_y >= 8.0  || square_y < 0.0 {
-                    None
-                } else {
-                    Some((square_x as usize, square_y as usize))
-                }
-            },
+        Some(panel.into())
+    }
+
+    /// Engine-vs-engine match setup, or its live score once running.
+    fn match_controls(&self) -> Option<Element<Message>> {
+        if self.engine.is_none() {
+            self.settings.engine_path.as_ref()?;
+        }
+
+        if let Some(engine_match) = &self.engine_match {
+            return Some(row![
+                text(format!(
+                    "game {}/{}   score {}",
+                    (engine_match.played + 1).min(engine_match.games),
+                    engine_match.games,
+                    engine_match.score(),
+                )),
+                button("Stop match").on_press(Message::StopMatch),
+            ].spacing(10).into());
         }
+
+        Some(column![
+            row![
+                text(format!("{} games", self.match_games)),
+                slider(1..=100, self.match_games, Message::MatchGamesChanged).width(150),
+            ].spacing(10),
+            row![
+                text(format!("{} ms/move", self.match_movetime_ms)),
+                slider(100..=10_000, self.match_movetime_ms, Message::MatchMovetimeChanged).step(100u32).width(150),
+            ].spacing(10),
+            button("Start engine match").on_press(Message::StartMatch),
+        ].into())
     }
 
-    fn view(&self) -> Element<Message> {
-        container(
+    fn puzzle_controls(&self) -> Element<Message> {
+        let Some(session) = &self.puzzle else {
+            return button("Load puzzles").on_press(Message::LoadPuzzles).into();
+        };
+
+        column![
+            text(format!(
+                "puzzle {}/{}   solved: {}   failed: {}",
+                session.current + 1,
+                session.puzzles.len(),
+                session.solved,
+                session.failed,
+            )),
             row![
-                Canvas::new(self).width(self.tile_size * 8.0).height(self.tile_size * 8.0),
-                text(format!(
-                    "
-                    selected: {:?}
-                    status: {:?}
-                    to play: {:?}
-                    white can castle: {:?}
-                    black can castle: {:?}
-                    state: {:?}
-                    ",
-                    self.selected,
-                    self.board.status(),
-                    self.board.side_to_move(),
-                    self.board.castle_rights(cozy_chess::Color::White).long != None && self.board.castle_rights(cozy_chess::Color::White).short != None,
-                    self.board.castle_rights(cozy_chess::Color::Black).long != None && self.board.castle_rights(cozy_chess::Color::Black).short != None,
-                    self.state,
-                )).size(25),
-            ].height(Fill)
-        ).into()
+                button("Next puzzle").on_press(Message::NextPuzzle),
+                button("Exit puzzles").on_press(Message::ExitPuzzles),
+            ].spacing(10),
+        ].into()
+    }
+
+    /// Opening drill setup, or the progress through the line once started.
+    fn drill_controls(&self) -> Element<Message> {
+        let Some(drill) = &self.drill else {
+            return column![
+                text_input("Opening line, e.g. 1. e4 e5 2. Nf3 Nc6", &self.drill_input)
+                    .on_input(Message::DrillLineChanged)
+                    .width(400),
+                row![
+                    radio("Practice White", cozy_chess::Color::White, Some(self.drill_side), Message::DrillSideSelected),
+                    radio("Practice Black", cozy_chess::Color::Black, Some(self.drill_side), Message::DrillSideSelected),
+                    button("Start drill").on_press(Message::StartDrill),
+                ].spacing(10),
+            ].spacing(5).into();
+        };
+
+        column![
+            text(format!(
+                "drill: {}/{} moves   best: {}",
+                drill.progress,
+                drill.line.len(),
+                drill.best,
+            )),
+            row![
+                button("Restart drill").on_press(Message::RestartDrill),
+                button("Exit drill").on_press(Message::ExitDrill),
+            ].spacing(10),
+        ].into()
+    }
+
+    /// Two-sided bar comparing how many legal moves each side has.
+    fn mobility_gauge(&self) -> Element<Message> {
+        let label = |count: Option<u32>| count.map_or("-".to_owned(), |count| count.to_string());
+        let segment = |count: Option<u32>, color: Color| {
+            container(text(label(count)).color(Color::from_rgb(0.5, 0.5, 0.5)))
+                .width(Length::FillPortion(count.unwrap_or(0).max(1) as u16))
+                .style(move |_| container::Style {
+                    background: Some(color.into()),
+                    ..container::Style::default()
+                })
+        };
+
+        column![
+            text("mobility").size(20),
+            row![
+                segment(self.mobility.white, Color::WHITE),
+                segment(self.mobility.black, Color::BLACK),
+            ].width(300),
+        ].into()
+    }
+
+    /// How many of each piece both sides have, in red where no legal game
+    /// could get there.
+    fn piece_counts(&self) -> Element<Message> {
+        let red = Color::from_rgb(0.9, 0.1, 0.1);
+        let cell = |content: String, bad: bool| {
+            text(content).font(Font::MONOSPACE).width(60).color_maybe(bad.then_some(red))
+        };
+
+        let mut table = column![
+            row![cell(String::new(), false)]
+                .extend(Piece::ALL.map(|piece| cell(format!("{piece:?}"), false).into()))
+                .push(cell("Total".to_owned(), false)),
+        ];
+        for color in cozy_chess::Color::ALL {
+            let counts = Piece::ALL.map(|piece| self.board.colored_pieces(color, piece).len() as usize);
+            let total: usize = counts.iter().sum();
+            let cells = Piece::ALL.into_iter().zip(counts).map(|(piece, count)| {
+                // promotions allow up to nine queens or ten of a minor piece or rook
+                let bad = match piece {
+                    Piece::Pawn => count > 8,
+                    Piece::Knight | Piece::Bishop | Piece::Rook => count > 10,
+                    Piece::Queen => count > 9,
+                    Piece::King => count != 1,
+                };
+                cell(count.to_string(), bad).into()
+            });
+            table = table.push(
+                row![cell(format!("{color:?}"), false)]
+                    .extend(cells)
+                    .push(cell(total.to_string(), total > 16)),
+            );
+        }
+
+        column![text("pieces").size(20), table].into()
+    }
+
+    /// The evaluation currently shown to the user, from White's perspective.
+    /// A finished search of this position takes priority over the heuristic.
+    fn eval(&self) -> Score {
+        match self.current_analysis() {
+            Some(result) => result.score,
+            None => eval::evaluate(&self.board),
+        }
+    }
+
+    fn current_analysis(&self) -> Option<&SearchResult> {
+        self.analysis.as_ref()
+            .filter(|(board, _)| board.same_position(&self.board))
+            .map(|(_, result)| result)
+    }
+
+    fn save_settings(&self) {
+        if let Err(err) = self.settings.save() {
+            eprintln!("failed to save settings: {err}");
+        }
     }
 
     fn canvas_coord_to_square_coord(&self, point: Point) -> (f32, f32) {
-        let square_x = (point.x) / self.tile_size;
-        let square_y = (point.y) / self.tile_size;
+        let square_x = (point.x - self.settings.board_margin) / self.tile_size;
+        let square_y = (point.y - self.settings.board_margin) / self.tile_size;
 
         (square_x, square_y)
     }
 
+    /// Width and height of the board, margins included.
+    fn board_size(&self) -> f32 {
+        self.tile_size * 8.0 + self.settings.board_margin * 2.0
+    }
+
+    /// Shift that centers the board in a canvas of `canvas_size`, leaving
+    /// even bars on the sides that are too long.
+    fn letterbox_offset(&self, canvas_size: Size) -> Vector {
+        Vector::new(
+            ((canvas_size.width - self.board_size()) / 2.0).max(0.0).floor(),
+            ((canvas_size.height - self.board_size()) / 2.0).max(0.0).floor(),
+        )
+    }
+
+    /// Top-left corner of the tile at (`x`, `y`), past the margin.
+    fn tile_origin(&self, x: usize, y: usize) -> Point {
+        let margin = self.settings.board_margin;
+        Point::new(margin + x as f32 * self.tile_size, margin + y as f32 * self.tile_size)
+    }
+
+    fn tile_rect(&self, x: usize, y: usize) -> Rectangle {
+        Rectangle::new(self.tile_origin(x, y), Size::new(self.tile_size, self.tile_size))
+    }
+
+    fn fill_tile(&self, frame: &mut Frame, x: usize, y: usize, color: Color) {
+        let top_left = self.tile_origin(x, y);
+        let size = Size::new(self.tile_size, self.tile_size);
+
+        frame.fill_rectangle(top_left, size, color);
+    }
+
+    fn tile_center(&self, square: Square) -> Point {
+        let (x, y) = self.tile_of(square);
+        let top_left = self.tile_origin(x, y);
+        Point::new(top_left.x + self.tile_size * 0.5, top_left.y + self.tile_size * 0.5)
+    }
+
+    /// Arrow between two square centers, `scale` times as thick as usual.
+    fn draw_arrow(&self, frame: &mut Frame, from: Square, to: Square, color: Color, scale: f32) {
+        let start = self.tile_center(from);
+        let end = self.tile_center(to);
+
+        let (dx, dy) = (end.x - start.x, end.y - start.y);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let (ux, uy) = (dx / length, dy / length);
+
+        let head_length = self.tile_size * 0.4 * scale;
+        let head_half_width = self.tile_size * 0.25 * scale;
+        let shaft_end = Point::new(end.x - ux * head_length, end.y - uy * head_length);
+
+        frame.stroke(
+            &Path::line(start, shaft_end),
+            Stroke::default().with_color(color).with_width(self.tile_size * 0.15 * scale),
+        );
+
+        let head = Path::new(|builder| {
+            builder.move_to(end);
+            builder.line_to(Point::new(shaft_end.x - uy * head_half_width, shaft_end.y + ux * head_half_width));
+            builder.line_to(Point::new(shaft_end.x + uy * head_half_width, shaft_end.y - ux * head_half_width));
+            builder.close();
+        });
+        frame.fill(&head, color);
+    }
+
     fn square_from_point(&mut self, point: Point) -> Option<Square> {
         let (square_x, square_y) = self.canvas_coord_to_square_coord(point);
         if square_x >= 8.0 || square_x < 0.0 || square_y >= 8.0  || square_y < 0.0 {
             None
         } else {
-            Some(coord_to_square(square_x as usize, square_y as usize))
+            Some(self.square_at(square_x as usize, square_y as usize))
+        }
+    }
+
+    /// Puts the human's pieces at the bottom when a bot game starts or the
+    /// sides are swapped. Later moves leave the orientation alone.
+    fn orient_for_bot_game(&mut self) {
+        if self.vs_bot && self.settings.orientation_lock.is_none() {
+            self.flipped = self.bot_color == cozy_chess::Color::White;
+        }
+    }
+
+    /// Whether Black is shown at the bottom. A locked orientation overrides
+    /// every automatic or manual flip.
+    fn is_flipped(&self) -> bool {
+        match self.settings.orientation_lock {
+            Some(color) => color == cozy_chess::Color::Black,
+            None => self.flipped,
+        }
+    }
+
+    /// Square shown on the tile at `(x, y)`, taking board orientation into account.
+    fn square_at(&self, x: usize, y: usize) -> Square {
+        if self.is_flipped() {
+            coord_to_square(7 - x, 7 - y)
+        } else {
+            coord_to_square(x, y)
+        }
+    }
+
+    /// Tile on which `square` is shown, taking board orientation into account.
+    fn tile_of(&self, square: Square) -> (usize, usize) {
+        let (x, y) = index_to_coord(square as usize);
+        if self.is_flipped() {
+            (7 - x, 7 - y)
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Remembers the FEN the board had before its last change, so the
+    /// fields a move altered can be shown.
+    fn track_fen(&mut self) {
+        if !self.debug_tools {
+            self.previous_fen = None;
+            self.current_fen.clear();
+            return;
+        }
+
+        let fen = fen::write_fen(&self.board, false);
+        if fen != self.current_fen {
+            let previous = std::mem::replace(&mut self.current_fen, fen);
+            self.previous_fen = (!previous.is_empty()).then_some(previous);
+        }
+    }
+
+    /// The FEN fields that changed with the last change to the board.
+    fn fen_diff_panel(&self) -> Option<Element<Message>> {
+        let previous = self.previous_fen.as_ref().filter(|_| self.debug_tools)?;
+        let mut panel = column![text("FEN changes:").font(Font::MONOSPACE)];
+        for change in fen::diff(previous, &self.current_fen) {
+            panel = panel.push(text(format!("  {}: {} -> {}", change.field, change.before, change.after))
+                .font(Font::MONOSPACE));
+        }
+        Some(panel.into())
+    }
+
+    /// Whether the position has a single good move, for authoring puzzles.
+    fn only_move_report(&self) -> Option<Element<Message>> {
+        let (hash, only_move) = self.only_move.as_ref().filter(|_| self.debug_tools)?;
+        if *hash != self.board.hash() {
+            return None;
         }
+
+        let best = only_move.best_move
+            .map_or("-".to_owned(), |mv| san::to_san(&self.board, mv));
+        let margin = only_move.margin.map_or("-".to_owned(), |margin| format!("{:+.2}", margin as f32 / 100.0));
+        let verdict = if only_move.is_forced() { "forced" } else { "not forced" };
+        Some(text(format!(
+            "legal moves: {}  best: {best}  ahead by: {margin}  ({verdict}, depth {ONLY_MOVE_DEPTH})",
+            only_move.legal_moves,
+        )).font(Font::MONOSPACE).into())
     }
 }
 
 impl Default for VisualBoard {
     fn default() -> Self {
-        VisualBoard {
+        let settings = Settings::load();
+        let coordinate_color_input = settings::color_to_hex(settings.coordinate_color);
+        let bot = BotConfig::new(None);
+        let bot_rng = bot.rng();
+        let assets_dir = settings.assets_dir.clone().unwrap_or_else(render::default_assets_dir);
+
+        let mut visual_board = VisualBoard {
             cache: Cache::default(),
-            tile_size: 64.0,
+            tile_size: 0.0,
             light_color: Color::from_rgb8(250, 207, 207),
             dark_color: Color::from_rgb8(154, 122, 161),
             board: Board::default(),
@@ -184,7 +3919,95 @@ impl Default for VisualBoard {
             promotion_square: None,
             state: State::Playing,
             hovered_tile: None,
-        }
+            last_move: None,
+            settings,
+            debug_tools: false,
+            previous_fen: None,
+            current_fen: String::new(),
+            notice: None,
+            start_position: Board::default(),
+            move_list: Vec::new(),
+            gif_delay_ms: 1000,
+            mobility: Mobility::of(&Board::default()),
+            imbalance: Imbalance::default(),
+            analysis: None,
+            analyzing: false,
+            flipped: false,
+            vs_bot: false,
+            vs_engine: false,
+            bot_color: cozy_chess::Color::Black,
+            puzzle: None,
+            flash: None,
+            show_shortcuts: false,
+            audio: Audio::new(),
+            draw_offered_by: None,
+            game_end: None,
+            engine: None,
+            show_engine_options: false,
+            engine_match: None,
+            match_games: 10,
+            match_movetime_ms: 500,
+            variation: None,
+            debug_overlay: false,
+            bitboard_view: BitboardView::Occupied,
+            dirty: false,
+            pending_discard: None,
+            clock: None,
+            move_clocks: Vec::new(),
+            assets_dir: PathBuf::new(),
+            missing_assets: Vec::new(),
+            drill: None,
+            drill_input: String::new(),
+            drill_side: cozy_chess::Color::White,
+            eval_graph: None,
+            computing_eval_graph: false,
+            waiting_for_engine: None,
+            pending_duplicate_save: None,
+            annotations: BTreeMap::new(),
+            right_drag_from: None,
+            bot,
+            bot_rng,
+            cursor_position: None,
+            minimal_view: false,
+            window_size: WINDOW_SIZE,
+            premove: None,
+            dragging: false,
+            split_dragging: false,
+            typed_move_number: String::new(),
+            search_stop: Arc::new(AtomicBool::new(false)),
+            bot_stopped_at: None,
+            new_game_setup: None,
+            tinted_pieces: Vec::new(),
+            tint_warned: false,
+            move_input: String::new(),
+            move_input_history: Vec::new(),
+            move_input_recall: None,
+            move_input_focused: false,
+            move_input_hovered: false,
+            modifiers: keyboard::Modifiers::default(),
+            auto_promotion: None,
+            coordinate_color_input,
+            only_move: None,
+            only_move_pending: None,
+            saved_game: None,
+            show_diagnostics: false,
+            frame_stats: Cell::new(FrameStats::default()),
+            pgn_collection: None,
+            collection_game: None,
+            blunder: None,
+            drag_start: None,
+            move_reviews: None,
+            annotating: false,
+            preset_name_input: String::new(),
+            watched_pgn: None,
+            slide: None,
+            keyboard_cursor: None,
+            keyboard_cursor_shown: false,
+            game_mode: GameMode::Casual,
+        };
+        visual_board.set_assets_dir(assets_dir);
+        visual_board.update_tile_size();
+        visual_board
     }
 }
 
@@ -198,18 +4021,27 @@ impl canvas::Program<Message> for VisualBoard {
         bounds: Rectangle,
         cursor: mouse::Cursor,
     ) -> Option<canvas::Action<Message>> {
-        let cursor_position = cursor.position_in(bounds)?;
+        let cursor_position = cursor.position_in(bounds)? - self.letterbox_offset(bounds.size());
 
         match event {
             Event::Mouse(mouse::Event::ButtonPressed(button)) => match button {
                 mouse::Button::Left => Some(canvas::Action::publish(
                     Message::Clicked(cursor_position)
                 )),
+                mouse::Button::Right => Some(canvas::Action::publish(Message::RightPressed(cursor_position))),
                 _ => None,
             },
-            Event::Mouse(mouse::Event::CursorMoved { position }) => {
-                if self.state == State::Promoting {
-                    Some(canvas::Action::publish(Message::CursorMoved(position)))
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                Some(canvas::Action::publish(Message::Released(cursor_position)))
+            },
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => {
+                Some(canvas::Action::publish(Message::RightReleased(cursor_position)))
+            },
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                // hovering matters to the promotion picker, the threat preview and dragging
+                let previewing = self.settings.threat_preview && self.selected.is_some();
+                if self.state == State::Promoting || previewing || self.dragging || self.settings.show_cursor_square || self.keyboard_cursor_shown {
+                    Some(canvas::Action::publish(Message::CursorMoved(cursor_position)))
                 } else {
                     None
                 }
@@ -228,11 +4060,14 @@ impl canvas::Program<Message> for VisualBoard {
         _cursor: mouse::Cursor,
     ) -> Vec<Geometry> {
         // println!("drawing");
+        let draw_started = Instant::now();
         let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
+            frame.translate(self.letterbox_offset(bounds.size()));
+
             // draw base board
             for y in 0..8 {
                 for x in 0..8 {
-                    let top_left = Point::new(x as f32 * self.tile_size, y as f32 * self.tile_size);
+                    let top_left = self.tile_origin(x, y);
                     let size = Size::new(self.tile_size, self.tile_size);
 
                     let color = if (x+y)%2==0 {
@@ -245,6 +4080,92 @@ impl canvas::Program<Message> for VisualBoard {
                 }
             }
 
+            // files along the bottom and ranks down the left, in the margin
+            let margin = self.settings.board_margin;
+            let teaching = self.settings.teaching_coordinates;
+            let label_color = if teaching { self.settings.coordinate_color } else { Color::from_rgb(0.5, 0.5, 0.5) };
+            if margin > 0.0 {
+                let scale = if teaching { 0.95 } else { 0.7 };
+                let size = (margin * scale).min(self.tile_size * 0.4);
+                for i in 0..8 {
+                    let file = self.square_at(i, 7).file();
+                    let rank = self.square_at(0, i).rank();
+                    let file_origin = self.tile_origin(i, 8);
+                    let rank_origin = self.tile_origin(0, i);
+                    for (content, position) in [
+                        (file.to_string(), Point::new(file_origin.x + (self.tile_size - size * 0.6) / 2.0, file_origin.y + (margin - size) / 2.0)),
+                        (rank.to_string(), Point::new((margin - size * 0.6) / 2.0, rank_origin.y + (self.tile_size - size) / 2.0)),
+                    ] {
+                        frame.fill_text(canvas::Text {
+                            content,
+                            position,
+                            color: label_color,
+                            size: size.into(),
+                            ..canvas::Text::default()
+                        });
+                    }
+                }
+            }
+
+            // every square's name in its bottom left corner
+            if teaching {
+                let size = self.tile_size * 0.2;
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let origin = self.tile_origin(x, y);
+                        frame.fill_text(canvas::Text {
+                            content: self.square_at(x, y).to_string(),
+                            position: Point::new(origin.x + size * 0.2, origin.y + self.tile_size - size * 1.2),
+                            color: label_color,
+                            size: size.into(),
+                            ..canvas::Text::default()
+                        });
+                    }
+                }
+            }
+
+            let colors = &self.settings.highlight_colors;
+
+            if self.debug_tools && self.debug_overlay {
+                for square in self.bitboard_view.of(&self.board) {
+                    let (x, y) = self.tile_of(square);
+                    self.fill_tile(frame, x, y, colors.debug_overlay);
+                }
+            }
+
+            // draw last move
+            if let Some(mv) = self.last_move.filter(|_| self.settings.last_move_style.squares()) {
+                for square in [mv.from, mv.to] {
+                    let (x, y) = self.tile_of(square);
+                    self.fill_tile(frame, x, y, colors.last_move);
+                }
+            }
+
+            // draw check
+            if !self.board.checkers().is_empty() {
+                let king = self.board.king(self.board.side_to_move());
+                let (x, y) = self.tile_of(king);
+                self.fill_tile(frame, x, y, colors.check);
+            }
+
+            // what the opponent would attack if the hovered move were played
+            let previewing = self.settings.threat_preview && matches!(self.state, State::Playing | State::Puzzle);
+            if let Some(mv) = self.hovered_move().filter(|_| previewing) {
+                let mut after = self.board.clone();
+                after.play_unchecked(mv);
+                for square in position::attacked_squares(&after, after.side_to_move()) {
+                    let (x, y) = self.tile_of(square);
+                    self.fill_tile(frame, x, y, colors.threat);
+                }
+            }
+
+            if let Some(premove) = self.premove {
+                for square in [premove.from, premove.to] {
+                    let (x, y) = self.tile_of(square);
+                    self.fill_tile(frame, x, y, colors.premove);
+                }
+            }
+
             // draw selection
             let mut indicated_squares = None;
 
@@ -255,52 +4176,244 @@ impl canvas::Program<Message> for VisualBoard {
                 });
             }
 
-            if let Some(pm) = indicated_squares {
-                let bitboard = pm.to;
+            if let Some(pm) = &indicated_squares {
+                let bitboard = if self.settings.captures_only {
+                    pm.to.iter()
+                        .filter(|&to| position::is_capture(&self.board, Move { from: pm.from, to, promotion: None }))
+                        .fold(BitBoard::EMPTY, |captures, to| captures | to.bitboard())
+                } else {
+                    pm.to
+                };
 
                 for square in bitboard.iter() {
-                    let (x, y) = index_to_coord(square as usize);
+                    let (x, y) = self.tile_of(square);
+                    self.fill_tile(frame, x, y, colors.selection);
+                }
+            }
 
-                    let top_left = Point::new(x as f32 * self.tile_size, y as f32 * self.tile_size);
-                    let size = Size::new(self.tile_size, self.tile_size);
+            if let Some(selected_square) = self.selected.filter(|&square| self.board.colors(self.board.side_to_move()).has(square)) {
+                // squares the piece could reach if it weren't for pins or check
+                if self.settings.learner_hints {
+                    let legal = indicated_squares.as_ref().map_or(BitBoard::EMPTY, |pm| pm.to);
+                    let pruned = position::pseudo_legal_destinations(&self.board, selected_square) & !legal;
+                    for square in pruned {
+                        let (x, y) = self.tile_of(square);
+                        self.fill_tile(frame, x, y, colors.illegal_hint);
+                    }
+                }
 
-                    let color = Color::from_rgba(0.0, 0.0, 1.0, 0.5);
+                let (x, y) = self.tile_of(selected_square);
+                let top_left = self.tile_origin(x, y);
+                let size = Size::new(self.tile_size, self.tile_size);
+                frame.stroke(
+                    &Path::rectangle(top_left, size),
+                    Stroke::default().with_color(colors.selected_outline).with_width(3.0),
+                );
+            }
 
-                    frame.fill_rectangle(top_left, size, color);
+            // friendly pieces the selected piece protects
+            if let Some(selected_square) = self.selected.filter(|_| self.settings.show_defended) {
+                let friends = self.board.color_on(selected_square).map_or(BitBoard::EMPTY, |color| self.board.colors(color));
+                for square in position::piece_attacks(&self.board, selected_square) & friends {
+                    let (x, y) = self.tile_of(square);
+                    self.fill_tile(frame, x, y, colors.defended);
+                }
+            }
+
+            // a + on every destination where the selected piece gives check
+            if let Some(selected_square) = self.selected.filter(|_| self.settings.show_checking_moves) {
+                let mut checking = BitBoard::EMPTY;
+                self.board.generate_moves_for(selected_square.bitboard(), |moves| {
+                    for mv in moves {
+                        let mut after = self.board.clone();
+                        after.play_unchecked(mv);
+                        if !after.checkers().is_empty() {
+                            checking |= mv.to.bitboard();
+                        }
+                    }
+                    false
+                });
+
+                for square in checking {
+                    let (x, y) = self.tile_of(square);
+                    let top_left = self.tile_origin(x, y);
+                    frame.fill_text(canvas::Text {
+                        content: "+".to_owned(),
+                        position: Point::new(top_left.x + self.tile_size * 0.05, top_left.y),
+                        color: colors.check_mark,
+                        size: (self.tile_size * 0.4).into(),
+                        ..canvas::Text::default()
+                    });
+                }
+            }
+
+            if let Some((mv, _)) = self.flash {
+                for square in [mv.from, mv.to] {
+                    let (x, y) = self.tile_of(square);
+                    self.fill_tile(frame, x, y, colors.wrong_move);
                 }
             }
 
             // draw pieces
             for y in 0..8 {
                 for x in 0..8 {
-                    let square = coord_to_square(x, y);
+                    let square = self.square_at(x, y);
+                    if self.dragging && self.selected == Some(square) {
+                        continue;
+                    }
+                    if self.slide.is_some_and(|(mv, _)| mv.to == square) {
+                        continue;
+                    }
                     if let Some(piece) = self.board.piece_on(square) {
-                        let mut img_handle: String = "assets/monochrome/".to_owned();
-                        if let Some(color) = self.board.color_on(square) {
-                            match color {
-                                cozy_chess::Color::White => img_handle += "white/",
-                                cozy_chess::Color::Black => img_handle += "black/",
-                            }
-                        }
-
-                        match piece {
-                            cozy_chess::Piece::Pawn => img_handle += "pawn.png",
-                            cozy_chess::Piece::Knight => img_handle += "knight.png",
-                            cozy_chess::Piece::Bishop => img_handle += "bishop.png",
-                            cozy_chess::Piece::Rook => img_handle += "rook.png",
-                            cozy_chess::Piece::Queen => img_handle += "queen.png",
-                            cozy_chess::Piece::King => img_handle += "king.png",
-                        }
+                        let img_handle = self.piece_image(self.board.color_on(square).unwrap(), piece);
 
                         let img = Image::new(img_handle).filter_method(image::FilterMethod::Nearest).snap(true);
 
-                        frame.draw_image(Rectangle{
-                            x: x as f32 * self.tile_size,
-                            y: y as f32 * self.tile_size,
-                            width: 64.0,
-                            height: 64.0,
-                        }, img);
+                        frame.draw_image(self.tile_rect(x, y), img);
+                    }
+                }
+            }
+
+            // the newest move of a watched game, on its way to its square
+            if let Some((mv, started)) = self.slide {
+                if let (Some(piece), Some(color)) = (self.board.piece_on(mv.to), self.board.color_on(mv.to)) {
+                    let progress = (started.elapsed().as_secs_f32() / self.animation_duration(SLIDE_DURATION).as_secs_f32()).min(1.0);
+                    let (from_x, from_y) = self.tile_of(mv.from);
+                    let (to_x, to_y) = self.tile_of(mv.to);
+                    let from = self.tile_origin(from_x, from_y);
+                    let to = self.tile_origin(to_x, to_y);
+                    let position = Point::new(from.x + (to.x - from.x) * progress, from.y + (to.y - from.y) * progress);
+
+                    let img = Image::new(self.piece_image(color, piece)).filter_method(image::FilterMethod::Nearest);
+                    frame.draw_image(Rectangle::new(position, Size::new(self.tile_size, self.tile_size)), img);
+                }
+            }
+
+            // mark rooks that keep their castling right
+            if self.settings.show_castling_markers {
+                for square in position::castling_rooks(&self.board) {
+                    let (x, y) = self.tile_of(square);
+                    let top_left = self.tile_origin(x, y);
+                    let right = top_left.x + self.tile_size;
+                    let top = top_left.y;
+                    let marker_size = self.tile_size * 0.25;
+
+                    let marker = Path::new(|builder| {
+                        builder.move_to(Point::new(right, top));
+                        builder.line_to(Point::new(right - marker_size, top));
+                        builder.line_to(Point::new(right, top + marker_size));
+                        builder.close();
+                    });
+                    frame.fill(&marker, colors.castling_marker);
+                }
+            }
+
+            // recent moves up to the shown position, older ones fainter
+            if self.settings.move_trail {
+                let line = self.current_line();
+                let length = self.settings.move_trail_length as usize;
+                let first = line.len().saturating_sub(length);
+                let mut board = self.start_position.clone();
+                for (index, &mv) in line.iter().enumerate() {
+                    if index >= first {
+                        // castling shows the king's path rather than the king taking its rook
+                        let shown = position::to_standard_castling(&board, mv);
+                        let age = (line.len() - 1 - index) as f32;
+                        let color = Color { a: colors.arrow.a * (1.0 - age / length as f32), ..colors.arrow };
+                        self.draw_arrow(frame, shown.from, shown.to, color, 0.8);
+                    }
+                    board.play_unchecked(mv);
+                }
+            }
+
+            if let Some(mv) = self.last_move.filter(|_| self.settings.last_move_style.arrow()) {
+                self.draw_arrow(frame, mv.from, mv.to, colors.arrow, 1.0);
+            }
+
+            // what the review would have played in the position shown
+            let best_move = self.current_ply()
+                .and_then(|ply| self.move_reviews.as_ref()?.get(ply)?.best_move);
+            if let Some(mv) = best_move {
+                let mv = position::to_standard_castling(&self.board, mv);
+                self.draw_arrow(frame, mv.from, mv.to, colors.best_move_arrow, 1.0);
+            }
+
+            if let Some(mv) = self.blunder {
+                self.draw_arrow(frame, mv.from, mv.to, colors.blunder_arrow, 1.0);
+            }
+
+            // every legal move at once, for teaching
+            if self.settings.ghost_arrows {
+                let filter = self.settings.ghost_arrow_filter;
+                self.board.generate_moves(|moves| {
+                    if filter.allows(moves.piece) {
+                        for mv in moves {
+                            let mv = position::to_standard_castling(&self.board, mv);
+                            self.draw_arrow(frame, mv.from, mv.to, colors.ghost_arrow, 0.4);
+                        }
                     }
+                    false
+                });
+            }
+
+            // arrows and circles drawn by the user or loaded from PGN
+            if let Some(annotations) = self.current_ply().and_then(|ply| self.annotations.get(&ply)) {
+                for &(square, color) in &annotations.marked_squares {
+                    let circle = Path::circle(self.tile_center(square), self.tile_size * 0.45);
+                    frame.stroke(&circle, Stroke::default().with_color(color.color()).with_width(4.0));
+                }
+                for &(from, to, color) in &annotations.arrows {
+                    self.draw_arrow(frame, from, to, color.color(), 1.0);
+                }
+            }
+
+            // the dragged piece under the cursor, over a drop target showing
+            // whether it may land there
+            if let Some(from) = self.selected.filter(|_| self.dragging) {
+                if let Some((x, y)) = self.hovered_tile.filter(|&(x, y)| self.square_at(x, y) != from) {
+                    let color = if self.hovered_move().is_some() { colors.drop_legal } else { colors.drop_illegal };
+                    self.fill_tile(frame, x, y, color);
+                }
+
+                if let (Some(point), Some(piece), Some(color)) = (self.cursor_position, self.board.piece_on(from), self.board.color_on(from)) {
+                    let img = Image::new(self.piece_image(color, piece))
+                        .filter_method(image::FilterMethod::Nearest)
+                        .snap(true);
+                    let half = self.tile_size / 2.0;
+                    frame.draw_image(Rectangle::new(Point::new(point.x - half, point.y - half), Size::new(self.tile_size, self.tile_size)), img);
+                }
+            }
+
+            // a soft fill under the mouse and a bold outline under the keyboard
+            // cursor, so both read apart when they're on the same square
+            if self.settings.show_cursor_square && self.state != State::Promoting {
+                if let Some((x, y)) = self.hovered_tile {
+                    self.fill_tile(frame, x, y, colors.hover);
+                }
+            }
+            if let Some(square) = self.keyboard_cursor.filter(|_| self.keyboard_cursor_shown && self.state != State::Promoting) {
+                let (x, y) = self.tile_of(square);
+                let inset = self.tile_size * 0.05;
+                let top_left = self.tile_origin(x, y) + Vector::new(inset, inset);
+                let size = Size::new(self.tile_size - inset * 2.0, self.tile_size - inset * 2.0);
+                frame.stroke(
+                    &Path::rectangle(top_left, size),
+                    Stroke::default().with_color(colors.keyboard_cursor).with_width((self.tile_size * 0.08).max(3.0)),
+                );
+            }
+
+            // name of the hovered square, next to the cursor
+            if self.settings.show_cursor_square && self.state != State::Promoting {
+                if let (Some(point), Some((x, y))) = (self.cursor_position, self.hovered_tile) {
+                    let top_left = Point::new(point.x + 12.0, point.y + 12.0);
+                    frame.fill_rectangle(top_left, Size::new(28.0, 20.0), colors.cursor_label);
+                    frame.fill_text(canvas::Text {
+                        content: self.square_at(x, y).to_string(),
+                        position: Point::new(top_left.x + 4.0, top_left.y + 2.0),
+                        color: Color::WHITE,
+                        size: 14.0.into(),
+                        ..canvas::Text::default()
+                    });
                 }
             }
 
@@ -308,64 +4421,84 @@ impl canvas::Program<Message> for VisualBoard {
             if self.state == State::Promoting {
                 for y in 0..8 {
                     for x in 0..8 {
-                        let top_left = Point::new(x as f32 * self.tile_size, y as f32 * self.tile_size);
-                        let size = Size::new(self.tile_size, self.tile_size);
-
-                        let color = Color::from_rgba(0.0, 0.0, 0.0, 0.9);
-
-                        frame.fill_rectangle(top_left, size, color);
+                        self.fill_tile(frame, x, y, colors.promotion_backdrop);
                     }
                 }
 
-                // hovered tile
-                if let Some((x, y)) = self.hovered_tile {
-                    if (2..=5).contains(&x) && y == 4 {
-                        let top_left = Point::new(x as f32 * self.tile_size, y as f32 * self.tile_size);
-                        let size = Size::new(self.tile_size, self.tile_size);
-            
-                        let color = Color::from_rgba(0.0, 1.0, 0.0, 0.5);
-            
-                        frame.fill_rectangle(top_left, size, color);
+                // the four pieces in the promotion square's column
+                for ((x, y), piece) in self.promotion_choices() {
+                    if self.hovered_tile == Some((x, y)) {
+                        self.fill_tile(frame, x, y, colors.promotion_hover);
                     }
+                    let img = Image::new(render::promotion_asset_path(&self.assets_dir, piece)).filter_method(image::FilterMethod::Nearest).snap(true);
+                    frame.draw_image(self.tile_rect(x, y), img);
                 }
+            }
+        });
 
-                // draw the 4 promotion pieces
-                let img_handle: String = "assets/color/neutral/".to_owned();
+        let mut stats = self.frame_stats.get();
+        stats.draw_time = draw_started.elapsed();
+        if let Some(last_frame) = stats.last_frame {
+            stats.interval = draw_started.duration_since(last_frame);
+        }
+        stats.last_frame = Some(draw_started);
+        self.frame_stats.set(stats);
 
-                let img = Image::new(img_handle.clone()+"rook.png").filter_method(image::FilterMethod::Nearest).snap(true);
-                let rect = Rectangle{
-                    x: 2 as f32 * self.tile_size,
-                    y: 4 as f32 * self.tile_size,
-                    width: 64.0,
-                    height: 64.0,
-                };
-                frame.draw_image(rect, img);
-
-                let img = Image::new(img_handle.clone()+"knight.png").filter_method(image::FilterMethod::Nearest).snap(true);
-                frame.draw_image(Rectangle{
-                    x: 3 as f32 * self.tile_size,
-                    y: 4 as f32 * self.tile_size,
-                    width: 64.0,
-                    height: 64.0,
-                }, img);
-                
-                let img = Image::new(img_handle.clone()+"bishop.png").filter_method(image::FilterMethod::Nearest).snap(true);
-                frame.draw_image(Rectangle{
-                    x: 4 as f32 * self.tile_size,
-                    y: 4 as f32 * self.tile_size,
-                    width: 64.0,
-                    height: 64.0,
-                }, img);
-
-                let img = Image::new(img_handle.clone()+"queen.png").filter_method(image::FilterMethod::Nearest).snap(true);
-                frame.draw_image(Rectangle{
-                    x: 5 as f32 * self.tile_size,
-                    y: 4 as f32 * self.tile_size,
-                    width: 64.0,
-                    height: 64.0,
-                }, img);
-            }
+        if !(self.debug_tools && self.show_diagnostics) {
+            return vec![geometry];
+        }
+
+        // drawn outside the cache so it changes on every frame
+        let mut overlay = Frame::new(renderer, bounds.size());
+        let fps = if stats.interval.is_zero() { 0.0 } else { 1.0 / stats.interval.as_secs_f32() };
+        overlay.fill_text(canvas::Text {
+            content: format!("{fps:.0} fps  draw {:.2} ms", stats.draw_time.as_secs_f32() * 1000.0),
+            position: Point::new(4.0, 4.0),
+            color: Color { a: 1.0, ..self.settings.highlight_colors.debug_overlay },
+            size: 16.0.into(),
+            font: Font::MONOSPACE,
+            ..canvas::Text::default()
         });
-        vec![geometry]
+        vec![geometry, overlay.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirming_a_discard_runs_the_held_back_message() {
+        let mut visual_board = VisualBoard::default();
+        visual_board.dirty = true;
+        visual_board.play_on();
+        assert!(visual_board.saved_game.as_ref().is_some_and(|saved| saved.dirty));
+
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+        let _ = visual_board.update(Message::FenPasted(Some(fen.to_owned())));
+        assert!(visual_board.pending_discard.is_some());
+
+        let _ = visual_board.update(Message::DiscardConfirmed);
+        assert!(visual_board.pending_discard.is_none());
+        assert!(visual_board.board.same_position(&Board::from_fen(fen, false).unwrap()));
+    }
+
+    #[test]
+    fn a_click_outside_the_move_box_hands_up_and_down_back() {
+        let mut visual_board = VisualBoard::default();
+        visual_board.move_input_history.push("e4".to_owned());
+        let _ = visual_board.update(Message::MoveInputChanged(String::new()));
+        let _ = visual_board.update(Message::MoveInputRecall(true));
+        assert_eq!(visual_board.move_input, "e4");
+
+        let _ = visual_board.update(Message::MoveInputChanged(String::new()));
+        let _ = visual_board.update(Message::PointerPressed);
+        let _ = visual_board.update(Message::MoveInputRecall(true));
+        assert_eq!(visual_board.move_input, "");
+
+        let _ = visual_board.update(Message::MoveInputHovered(true));
+        let _ = visual_board.update(Message::PointerPressed);
+        let _ = visual_board.update(Message::MoveInputRecall(true));
+        assert_eq!(visual_board.move_input, "e4");
     }
 }
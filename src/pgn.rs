@@ -0,0 +1,327 @@
+use std::time::Duration;
+
+use cozy_chess::{Board, Move};
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+use crate::{fen, san};
+
+/// A complete PGN game. `headers` go before the `Result` tag, and a `FEN`
+/// tag is added when the game didn't start from the initial position.
+/// `comment(index)` is written in braces after move `index`.
+pub fn write_game(
+    headers: &[(&str, String)],
+    start: &Board,
+    shredder_fen: bool,
+    moves: &[Move],
+    comment: impl Fn(usize) -> Option<String>,
+    result: &str,
+) -> String {
+    let mut pgn = String::new();
+    for (tag, value) in headers {
+        pgn += &format!("[{tag} \"{value}\"]\n");
+    }
+    pgn += &format!("[Result \"{result}\"]\n");
+    if !start.same_position(&Board::default()) {
+        pgn += &format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", fen::write_fen(start, shredder_fen));
+    }
+
+    pgn.push('\n');
+    let movetext = san::annotated_move_list_text(start, moves, comment);
+    if !movetext.is_empty() {
+        pgn += &movetext;
+        pgn.push(' ');
+    }
+    pgn += result;
+    pgn += "\n\n";
+    pgn
+}
+
+/// Clock reading as a `[%clk H:MM:SS]` comment command.
+pub fn clock_comment(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("[%clk {}:{:02}:{:02}]", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// A game read from PGN text.
+#[derive(Debug, Clone)]
+pub struct ImportedGame {
+    pub start: Board,
+    pub moves: Vec<Move>,
+    /// Value of the `Result` tag, `*` when missing.
+    pub result: String,
+    /// Comments, each with the number of moves played before it.
+    pub comments: Vec<(usize, String)>,
+}
+
+/// Reads the first game of `pgn`, as exported by sites like Lichess or
+/// chess.com. Comments, variations, NAGs and annotation glyphs are ignored.
+pub fn read_game(pgn: &str) -> Result<ImportedGame, String> {
+    let mut result = "*".to_owned();
+    let mut start = Board::default();
+    let mut movetext = String::new();
+    let mut has_tags = false;
+    let mut in_movetext = false;
+
+    for line in pgn.lines() {
+        let line = line.trim();
+        let Some(tag) = line.strip_prefix('[').and_then(|tag| tag.strip_suffix(']')) else {
+            in_movetext |= !line.is_empty();
+            movetext += line;
+            movetext.push('\n');
+            continue;
+        };
+        // a tag after the movetext starts the next game
+        if in_movetext {
+            break;
+        }
+
+        let Some((name, value)) = tag.split_once(' ') else {
+            continue;
+        };
+        has_tags = true;
+        let value = value.trim().trim_matches('"');
+        match name {
+            "Result" => result = value.to_owned(),
+            "FEN" => {
+                start = fen::parse_fen(value)
+                    .map_err(|problems| format!("invalid FEN tag: {}", problems.join("; ")))?;
+            },
+            _ => {},
+        }
+    }
+
+    let (moves, comments) = read_annotated_movetext(&start, &movetext)?;
+
+    if moves.is_empty() && !has_tags {
+        return Err("no game found".to_owned());
+    }
+
+    Ok(ImportedGame { start, moves, result, comments })
+}
+
+/// Players and result of one game of a collection, read from its tags
+/// without touching the movetext.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameHeader {
+    /// Position of the game in its file, starting at 0.
+    pub index: usize,
+    pub white: String,
+    pub black: String,
+    pub result: String,
+}
+
+impl std::fmt::Display for GameHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}. {} vs {} ({})", self.index + 1, self.white, self.black, self.result)
+    }
+}
+
+/// Reads the tags at the top of `game`, stopping at the movetext.
+pub fn read_header(index: usize, game: &str) -> GameHeader {
+    let mut header = GameHeader {
+        index,
+        white: "?".to_owned(),
+        black: "?".to_owned(),
+        result: "*".to_owned(),
+    };
+
+    for line in game.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let Some(tag) = line.strip_prefix('[').and_then(|tag| tag.strip_suffix(']')) else {
+            break;
+        };
+        let Some((name, value)) = tag.split_once(' ') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_owned();
+        match name {
+            "White" => header.white = value,
+            "Black" => header.black = value,
+            "Result" => header.result = value,
+            _ => {},
+        }
+    }
+
+    header
+}
+
+/// The games of a PGN file, with only their tags read. The movetext of a
+/// game is left for [`read_game`] once it's picked.
+#[derive(Debug, Clone, Default)]
+pub struct Collection {
+    pub headers: Vec<GameHeader>,
+    pub games: Vec<String>,
+}
+
+pub fn read_collection(pgn: &str) -> Collection {
+    let games: Vec<String> = split_games(pgn).into_iter().map(str::to_owned).collect();
+    let headers = games.iter().enumerate().map(|(index, game)| read_header(index, game)).collect();
+    Collection { headers, games }
+}
+
+/// Splits a PGN file holding several games into the text of each game.
+pub fn split_games(pgn: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let mut game_start = 0;
+    let mut in_movetext = false;
+    let mut offset = 0;
+
+    for line in pgn.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && in_movetext {
+            games.push(&pgn[game_start..offset]);
+            game_start = offset;
+            in_movetext = false;
+        } else if !trimmed.is_empty() && !trimmed.starts_with('[') {
+            in_movetext = true;
+        }
+        offset += line.len();
+    }
+    if !pgn[game_start..].trim().is_empty() {
+        games.push(&pgn[game_start..]);
+    }
+
+    games
+}
+
+/// Replays the SAN moves of `movetext` from `start`, such as `1. e4 e5 2. Nf3`.
+pub fn read_movetext(start: &Board, movetext: &str) -> Result<Vec<Move>, String> {
+    read_annotated_movetext(start, movetext).map(|(moves, _)| moves)
+}
+
+/// Like [`read_movetext`], also returning the comments of the main line,
+/// each with the number of moves played before it.
+pub fn read_annotated_movetext(start: &Board, movetext: &str) -> Result<(Vec<Move>, Vec<(usize, String)>), String> {
+    let mut board = start.clone();
+    let mut moves = Vec::new();
+    let mut comments = Vec::new();
+    for token in movetext_tokens(movetext) {
+        match token {
+            MovetextToken::San(san) => {
+                let mv = san::parse_san(&board, &san).ok_or_else(|| format!("illegal move {san}"))?;
+                board.play_unchecked(mv);
+                moves.push(mv);
+            },
+            MovetextToken::Comment(comment) => comments.push((moves.len(), comment)),
+        }
+    }
+    Ok((moves, comments))
+}
+
+enum MovetextToken {
+    San(String),
+    Comment(String),
+}
+
+/// The SAN moves and comments of `movetext`, with variations, NAGs, `!?`
+/// glyphs, move numbers and the result stripped away.
+fn movetext_tokens(movetext: &str) -> Vec<MovetextToken> {
+    let mut cleaned = String::new();
+    let mut comments = Vec::new();
+    let mut comment: Option<String> = None;
+    let mut in_line_comment = false;
+    let mut variation_depth = 0u32;
+
+    for c in movetext.chars() {
+        if let Some(text) = &mut comment {
+            if c != '}' {
+                text.push(c);
+                continue;
+            }
+            // stands in for the comment so it keeps its place among the moves
+            if variation_depth == 0 {
+                cleaned += &format!(" {{{}}} ", comments.len());
+                comments.push(text.trim().to_owned());
+            }
+            comment = None;
+            continue;
+        }
+        if in_line_comment {
+            in_line_comment = c != '\n';
+            continue;
+        }
+
+        match c {
+            '{' => comment = Some(String::new()),
+            ';' => in_line_comment = true,
+            '(' => variation_depth += 1,
+            ')' => variation_depth = variation_depth.saturating_sub(1),
+            _ if variation_depth > 0 => continue,
+            _ => {
+                cleaned.push(c);
+                continue;
+            },
+        }
+        cleaned.push(' ');
+    }
+
+    let mut tokens = Vec::new();
+    for token in cleaned.split_whitespace() {
+        let comment_index = token.strip_prefix('{')
+            .and_then(|token| token.strip_suffix('}'))
+            .and_then(|index| index.parse::<usize>().ok());
+        if let Some(index) = comment_index {
+            tokens.push(MovetextToken::Comment(comments[index].clone()));
+            continue;
+        }
+
+        // move numbers, either alone (`12.`, `12...`) or glued to the move (`12.e4`)
+        let token = match token.find('.') {
+            Some(dot) if token[..dot].chars().all(|c| c.is_ascii_digit()) => token[dot..].trim_start_matches('.'),
+            _ => token,
+        };
+        let token = token.trim_end_matches(['!', '?']);
+
+        if token.is_empty() || token.starts_with('$') || RESULTS.contains(&token) {
+            continue;
+        }
+        tokens.push(MovetextToken::San(token.to_owned()));
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The moves of `sans` played from the initial position.
+    fn moves(sans: &[&str]) -> Vec<Move> {
+        let mut board = Board::default();
+        sans.iter()
+            .map(|san| {
+                let mv = san::parse_san(&board, san).unwrap();
+                board.play(mv);
+                mv
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_only_the_first_game() {
+        let pgn = "[Result \"1-0\"]\n\n1. e4 e5 1-0\n\n[Result \"0-1\"]\n\n1. d4 d5 0-1\n";
+        let game = read_game(pgn).unwrap();
+        assert_eq!(game.moves, moves(&["e4", "e5"]));
+        assert_eq!(game.result, "1-0");
+    }
+
+    #[test]
+    fn skips_annotations_and_variations() {
+        let pgn = "[Event \"Live Chess\"]\n[White \"a\"]\n[Black \"b\"]\n[Result \"1-0\"]\n\n\
+            1. e4 {[%clk 0:04:59.9]} 1... e5 {[%clk 0:04:58]} 2. Nf3 $1 Nc6!? \
+            3. Bb5 (3. Bc4 Bc5 (3... Nf6 4. Ng5)) 3...a6 1-0";
+        let game = read_game(pgn).unwrap();
+        assert_eq!(game.moves, moves(&["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.comments, vec![
+            (1, "[%clk 0:04:59.9]".to_owned()),
+            (2, "[%clk 0:04:58]".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn rejects_text_without_a_game() {
+        assert!(read_game("").is_err());
+        assert!(read_game("1. e4 Ke7??").is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use cozy_chess::{
+    BitBoard, Board, BoardBuilder, BoardBuilderError, Color, File, Move, Piece, Rank, Square,
+    get_bishop_moves,
+    get_king_moves, get_knight_moves, get_pawn_attacks, get_pawn_quiets, get_rook_moves,
+};
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+/// The same position with the other side to move, as if a null move was played.
+pub fn flip_side_to_move(board: &Board) -> Result<Board, BoardBuilderError> {
+    let mut builder = BoardBuilder::from_board(board);
+    builder.side_to_move = !builder.side_to_move;
+    builder.en_passant = None;
+    builder.build()
+}
+
+pub fn count_legal_moves(board: &Board) -> u32 {
+    let mut count = 0;
+    board.generate_moves(|moves| {
+        count += moves.len() as u32;
+        false
+    });
+    count
+}
+
+/// Legal move counts for both sides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Mobility {
+    pub white: Option<u32>,
+    pub black: Option<u32>,
+}
+
+impl Mobility {
+    /// The side not to move is left as `None` while the side to move is in
+    /// check, because handing it the move would give an illegal position.
+    pub fn of(board: &Board) -> Self {
+        let to_move = Some(count_legal_moves(board));
+        let waiting = flip_side_to_move(board).ok().map(|flipped| count_legal_moves(&flipped));
+
+        match board.side_to_move() {
+            Color::White => Mobility { white: to_move, black: waiting },
+            Color::Black => Mobility { white: waiting, black: to_move },
+        }
+    }
+}
+
+/// Every square the piece on `square` could move to if pins and checks were
+/// ignored. Castling and en passant are left out.
+pub fn pseudo_legal_destinations(board: &Board, square: Square) -> BitBoard {
+    let (Some(piece), Some(color)) = (board.piece_on(square), board.color_on(square)) else {
+        return BitBoard::EMPTY;
+    };
+
+    let blockers = board.occupied();
+    let targets = match piece {
+        Piece::Pawn => {
+            get_pawn_quiets(square, color, blockers)
+                | (get_pawn_attacks(square, color) & board.colors(!color))
+        },
+        Piece::Knight => get_knight_moves(square),
+        Piece::Bishop => get_bishop_moves(square, blockers),
+        Piece::Rook => get_rook_moves(square, blockers),
+        Piece::Queen => get_bishop_moves(square, blockers) | get_rook_moves(square, blockers),
+        Piece::King => get_king_moves(square),
+    };
+
+    targets & !board.colors(color)
+}
+
+/// Castling rights in FEN notation, e.g. `KQkq`, `Kq` or `-`.
+pub fn castling_rights_string(board: &Board) -> String {
+    let mut rights = String::new();
+    for (color, short, long) in [(Color::White, 'K', 'Q'), (Color::Black, 'k', 'q')] {
+        let castle_rights = board.castle_rights(color);
+        if castle_rights.short.is_some() {
+            rights.push(short);
+        }
+        if castle_rights.long.is_some() {
+            rights.push(long);
+        }
+    }
+
+    if rights.is_empty() {
+        rights.push('-');
+    }
+    rights
+}
+
+/// Parses a move in UCI notation, where castling is written as the king
+/// moving two squares rather than cozy_chess' king-takes-rook.
+pub fn parse_uci_move(board: &Board, uci: &str) -> Option<Move> {
+    let mut mv: Move = uci.parse().ok()?;
+
+    let us = board.side_to_move();
+    if board.colored_pieces(us, Piece::King).has(mv.from) {
+        let rights = board.castle_rights(us);
+        let rook_file = match (mv.from.file(), mv.to.file()) {
+            (File::E, File::G) => rights.short,
+            (File::E, File::C) => rights.long,
+            _ => None,
+        };
+        if let Some(file) = rook_file {
+            mv.to = Square::new(file, mv.to.rank());
+        }
+    }
+
+    board.is_legal(mv).then_some(mv)
+}
+
+/// `mv` with castling written as the king moving two squares, the way it is
+/// shown to users and sent to UCI engines. `board` is the position before `mv`.
+pub fn to_standard_castling(board: &Board, mv: Move) -> Move {
+    let us = board.side_to_move();
+    let castles = board.colored_pieces(us, Piece::King).has(mv.from)
+        && board.colored_pieces(us, Piece::Rook).has(mv.to);
+    if !castles {
+        return mv;
+    }
+
+    let file = if mv.to.file() as usize > mv.from.file() as usize { File::G } else { File::C };
+    Move {
+        from: mv.from,
+        to: Square::new(file, mv.from.rank()),
+        promotion: None,
+    }
+}
+
+/// Plays up to `plies` random legal moves from the starting position,
+/// stopping early if the game ends.
+pub fn random_position(rng: &mut impl Rng, plies: usize) -> Board {
+    let mut board = Board::default();
+
+    for _ in 0..plies {
+        let mut moves = Vec::new();
+        board.generate_moves(|piece_moves| {
+            moves.extend(piece_moves);
+            false
+        });
+
+        let Some(&mv) = moves.choose(rng) else {
+            break;
+        };
+        board.play_unchecked(mv);
+    }
+
+    board
+}
+
+/// Whether `mv` takes a piece, en passant included.
+pub fn is_capture(board: &Board, mv: Move) -> bool {
+    let them = board.colors(!board.side_to_move());
+    if them.has(mv.to) {
+        return true;
+    }
+
+    let en_passant = board.en_passant().map(|file| {
+        Square::new(file, Rank::Sixth.relative_to(board.side_to_move()))
+    });
+    board.pieces(Piece::Pawn).has(mv.from) && Some(mv.to) == en_passant
+}
+
+/// Hashes of every position of a game, the starting one included.
+pub fn position_hashes(start: &Board, moves: &[Move]) -> Vec<u64> {
+    let mut board = start.clone();
+    let mut hashes = vec![board.hash()];
+    for &mv in moves {
+        board.play_unchecked(mv);
+        hashes.push(board.hash());
+    }
+    hashes
+}
+
+/// Neither side has enough material left to ever deliver mate: a single
+/// minor piece at most, or only bishops all standing on one square colour.
+pub fn insufficient_material(board: &Board) -> bool {
+    let heavy = board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    let minors = board.pieces(Piece::Knight) | board.pieces(Piece::Bishop);
+    if !heavy.is_empty() {
+        return false;
+    }
+
+    let square_color = |square: Square| (square.file() as usize + square.rank() as usize) % 2;
+    let mut bishop_colors = board.pieces(Piece::Bishop).iter().map(square_color);
+    let same_colored_bishops = board.pieces(Piece::Knight).is_empty()
+        && bishop_colors.next().is_none_or(|first| bishop_colors.all(|color| color == first));
+    minors.len() <= 1 || same_colored_bishops
+}
+
+/// Rooks that can still castle, for both sides.
+pub fn castling_rooks(board: &Board) -> BitBoard {
+    let mut rooks = BitBoard::EMPTY;
+    for color in Color::ALL {
+        let rights = board.castle_rights(color);
+        let back_rank = Rank::First.relative_to(color);
+        for file in [rights.short, rights.long].into_iter().flatten() {
+            rooks |= Square::new(file, back_rank).bitboard();
+        }
+    }
+    rooks
+}
+
+/// The premove queued during the opponent's turn, if it is still legal on
+/// `board` now that the opponent has replied.
+pub fn validate_premove(board: &Board, premove: Move) -> Option<Move> {
+    board.is_legal(premove).then_some(premove)
+}
+
+/// Every square attacked by a piece of `color`, whether or not the attack
+/// could be played as a legal move.
+pub fn attacked_squares(board: &Board, color: Color) -> BitBoard {
+    let mut attacks = BitBoard::EMPTY;
+    for square in board.colors(color) {
+        attacks |= piece_attacks(board, square);
+    }
+    attacks
+}
+
+/// Squares the piece on `square` attacks, friendly pieces included, so the
+/// ones it defends can be picked out.
+pub fn piece_attacks(board: &Board, square: Square) -> BitBoard {
+    let (Some(piece), Some(color)) = (board.piece_on(square), board.color_on(square)) else {
+        return BitBoard::EMPTY;
+    };
+
+    let blockers = board.occupied();
+    match piece {
+        Piece::Pawn => get_pawn_attacks(square, color),
+        Piece::Knight => get_knight_moves(square),
+        Piece::Bishop => get_bishop_moves(square, blockers),
+        Piece::Rook => get_rook_moves(square, blockers),
+        Piece::Queen => get_bishop_moves(square, blockers) | get_rook_moves(square, blockers),
+        Piece::King => get_king_moves(square),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen, false).or_else(|_| Board::from_fen(fen, true)).unwrap()
+    }
+
+    #[test]
+    fn castling_rights_string_lists_each_right() {
+        assert_eq!(castling_rights_string(&Board::default()), "KQkq");
+        assert_eq!(castling_rights_string(&board("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1")), "Kq");
+        assert_eq!(castling_rights_string(&board("4k3/8/8/8/8/8/8/4K3 w - - 0 1")), "-");
+    }
+
+    #[test]
+    fn castling_rights_string_names_chess960_rights_by_side() {
+        let chess960 = board("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1");
+        assert_eq!(castling_rights_string(&chess960), "KQkq");
+
+        let short_only = board("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w Hh - 0 1");
+        assert_eq!(castling_rights_string(&short_only), "Kk");
+    }
+
+    #[test]
+    fn insufficient_material_allows_bishops_on_one_color() {
+        assert!(insufficient_material(&board("4k3/8/8/8/8/8/8/4K3 w - - 0 1")));
+        assert!(insufficient_material(&board("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1")));
+        // c1 and f8 are both dark squares
+        assert!(insufficient_material(&board("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1")));
+
+        assert!(!insufficient_material(&board("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1")));
+        assert!(!insufficient_material(&board("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1")));
+        assert!(!insufficient_material(&board("4k3/8/8/8/8/8/8/2B1KN2 w - - 0 1")));
+        assert!(!insufficient_material(&board("4k3/8/8/8/8/8/P7/4K3 w - - 0 1")));
+    }
+
+    #[test]
+    fn premove_is_dropped_once_the_reply_pins_it() {
+        let premove: Move = "d2f3".parse().unwrap();
+        let waiting = board("4kb2/8/8/8/8/8/3N4/4K3 b - - 0 1");
+
+        let mut pinned = waiting.clone();
+        pinned.play("f8b4".parse().unwrap());
+        assert_eq!(validate_premove(&pinned, premove), None);
+
+        let mut free = waiting.clone();
+        free.play("e8d8".parse().unwrap());
+        assert_eq!(validate_premove(&free, premove), Some(premove));
+    }
+}
@@ -0,0 +1,120 @@
+use cozy_chess::{Board, Move};
+
+use crate::position;
+
+/// A tactic to solve. The solver is the side to move in `start`, and
+/// `solution` alternates between the solver's moves and the forced replies.
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    pub start: Board,
+    pub solution: Vec<Move>,
+}
+
+/// Parses one puzzle per line, written as `<fen>;<uci moves>`.
+/// Blank lines and lines starting with `#` are skipped.
+pub fn parse_puzzles(contents: &str) -> Result<Vec<Puzzle>, String> {
+    let mut puzzles = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = |reason: &str| format!("line {}: {reason}", number + 1);
+
+        let (fen, moves) = line.split_once(';').ok_or_else(|| error("expected `<fen>;<moves>`"))?;
+        let start = Board::from_fen(fen.trim(), false).map_err(|_| error("invalid FEN"))?;
+
+        let mut board = start.clone();
+        let mut solution = Vec::new();
+        for uci in moves.split_whitespace() {
+            let mv = position::parse_uci_move(&board, uci)
+                .ok_or_else(|| error(&format!("illegal move {uci}")))?;
+            board.play_unchecked(mv);
+            solution.push(mv);
+        }
+
+        if solution.is_empty() {
+            return Err(error("puzzle has no solution"));
+        }
+
+        puzzles.push(Puzzle { start, solution });
+    }
+
+    Ok(puzzles)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attempt {
+    /// Right move, the puzzle goes on with this reply.
+    Correct(Move),
+    Solved,
+    Wrong,
+}
+
+#[derive(Debug)]
+pub struct PuzzleSession {
+    pub puzzles: Vec<Puzzle>,
+    pub current: usize,
+    /// Number of moves of the current solution already played.
+    pub progress: usize,
+    pub solved: u32,
+    pub failed: u32,
+}
+
+impl PuzzleSession {
+    pub fn new(puzzles: Vec<Puzzle>) -> Self {
+        PuzzleSession {
+            puzzles,
+            current: 0,
+            progress: 0,
+            solved: 0,
+            failed: 0,
+        }
+    }
+
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzles[self.current]
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.progress == self.puzzle().solution.len()
+    }
+
+    /// Checks the solver's move `mv`, played on `board`. Any move that mates
+    /// is accepted, since puzzles often have several mates in one.
+    pub fn attempt(&mut self, board: &Board, mv: Move) -> Attempt {
+        let expected = self.puzzle().solution[self.progress];
+
+        let mut after = board.clone();
+        let mates = after.try_play(mv).is_ok() && after.status() == cozy_chess::GameStatus::Won;
+
+        if mv != expected && !mates {
+            self.failed += 1;
+            self.progress = 0;
+            return Attempt::Wrong;
+        }
+
+        self.progress += 1;
+        if mates || self.is_solved() {
+            self.progress = self.puzzle().solution.len();
+            self.solved += 1;
+            return Attempt::Solved;
+        }
+
+        let reply = self.puzzle().solution[self.progress];
+        self.progress += 1;
+        Attempt::Correct(reply)
+    }
+
+    /// Moves on to the next puzzle, returning false after the last one.
+    pub fn advance(&mut self) -> bool {
+        if self.current + 1 >= self.puzzles.len() {
+            return false;
+        }
+        self.current += 1;
+        self.progress = 0;
+        true
+    }
+}
@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use cozy_chess::{Board, Move, Piece, Square};
+use iced::Color;
+use image::{RgbaImage, imageops};
+
+/// `assets` next to the executable, falling back to the working directory
+/// so running from a source checkout keeps working.
+pub fn default_assets_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("assets")))
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(|| PathBuf::from("assets"))
+}
+
+fn piece_file_name(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "pawn.png",
+        Piece::Knight => "knight.png",
+        Piece::Bishop => "bishop.png",
+        Piece::Rook => "rook.png",
+        Piece::Queen => "queen.png",
+        Piece::King => "king.png",
+    }
+}
+
+/// Path of the image used for a piece on the board.
+pub fn piece_asset_path(assets_dir: &Path, color: cozy_chess::Color, piece: Piece) -> PathBuf {
+    let color_dir = match color {
+        cozy_chess::Color::White => "white",
+        cozy_chess::Color::Black => "black",
+    };
+    assets_dir.join("monochrome").join(color_dir).join(piece_file_name(piece))
+}
+
+/// Path of the single-color image used for both sides when a piece set
+/// doesn't ship one image per color.
+pub fn neutral_asset_path(assets_dir: &Path, piece: Piece) -> PathBuf {
+    assets_dir.join("monochrome").join("neutral").join(piece_file_name(piece))
+}
+
+/// Image of a piece, tinted from the neutral image when the set has none for
+/// `color`. Also returns whether the tinted fallback was used.
+pub fn load_piece_image(assets_dir: &Path, color: cozy_chess::Color, piece: Piece) -> Result<(RgbaImage, bool), String> {
+    let path = piece_asset_path(assets_dir, color, piece);
+    if path.is_file() {
+        let img = image::open(&path).map_err(|err| format!("couldn't load {}: {err}", path.display()))?;
+        return Ok((img.to_rgba8(), false));
+    }
+
+    let neutral = neutral_asset_path(assets_dir, piece);
+    let mut img = image::open(&neutral)
+        .map_err(|err| format!("couldn't load {} or {}: {err}", path.display(), neutral.display()))?
+        .to_rgba8();
+    tint(&mut img, color);
+    Ok((img, true))
+}
+
+/// Darkens the image for Black and lightens it for White, leaving transparency alone.
+fn tint(img: &mut RgbaImage, color: cozy_chess::Color) {
+    for pixel in img.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = match color {
+                cozy_chess::Color::White => (*channel as f32 * 0.4 + 255.0 * 0.6) as u8,
+                cozy_chess::Color::Black => (*channel as f32 * 0.35) as u8,
+            };
+        }
+    }
+}
+
+/// Path of the image offered in the promotion picker.
+pub fn promotion_asset_path(assets_dir: &Path, piece: Piece) -> PathBuf {
+    assets_dir.join("color").join("neutral").join(piece_file_name(piece))
+}
+
+/// Every image the board needs that isn't in `assets_dir`. A piece with a
+/// neutral image isn't missing, as it gets tinted for either side.
+pub fn missing_assets(assets_dir: &Path) -> Vec<PathBuf> {
+    let pieces = cozy_chess::Color::ALL.into_iter()
+        .flat_map(|color| Piece::ALL.map(|piece| (color, piece)))
+        .filter(|&(_, piece)| !neutral_asset_path(assets_dir, piece).is_file())
+        .map(|(color, piece)| piece_asset_path(assets_dir, color, piece));
+    let promotions = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .map(|piece| promotion_asset_path(assets_dir, piece));
+
+    pieces.chain(promotions).filter(|path| !path.is_file()).collect()
+}
+
+/// Colors used when drawing a position outside of the canvas.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardColors {
+    pub light: Color,
+    pub dark: Color,
+    pub last_move: Color,
+    /// Color of the teaching coordinates, which are only drawn when set.
+    pub coordinates: Option<Color>,
+}
+
+/// 5x7 bitmaps of the characters used in coordinates, one row per byte with
+/// the leftmost pixel in bit 4.
+fn glyph(c: char) -> Option<[u8; 7]> {
+    Some(match c {
+        'a' => [0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111],
+        'b' => [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110],
+        'c' => [0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110],
+        'd' => [0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111],
+        'e' => [0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110],
+        'f' => [0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000],
+        'g' => [0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110],
+        'h' => [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        _ => return None,
+    })
+}
+
+/// Writes `label` with its top left corner at (`x`, `y`), each font pixel
+/// drawn as a `scale` sized block. Pixels outside the image are dropped.
+fn draw_label(img: &mut RgbaImage, label: &str, x: u32, y: u32, scale: u32, color: Color) {
+    let [r, g, b, a] = color.into_rgba8();
+    for (i, c) in label.chars().enumerate() {
+        let Some(rows) = glyph(c) else {
+            continue;
+        };
+        let left = x + i as u32 * 6 * scale;
+        for (row, bits) in rows.into_iter().enumerate() {
+            for column in 0..5 {
+                if bits & (0b10000 >> column) == 0 {
+                    continue;
+                }
+                for py in 0..scale {
+                    for px in 0..scale {
+                        let (px, py) = (left + column * scale + px, y + row as u32 * scale + py);
+                        if px < img.width() && py < img.height() {
+                            img.put_pixel(px, py, image::Rgba([r, g, b, a]));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Software counterpart of the canvas drawing, used for image exports.
+pub struct BoardRenderer {
+    tile_size: u32,
+    colors: BoardColors,
+    pieces: Vec<(cozy_chess::Color, Piece, RgbaImage)>,
+}
+
+impl BoardRenderer {
+    pub fn new(assets_dir: &Path, tile_size: u32, colors: BoardColors) -> Result<Self, Box<dyn Error>> {
+        let mut pieces = Vec::new();
+        for color in cozy_chess::Color::ALL {
+            for piece in Piece::ALL {
+                let (img, _) = load_piece_image(assets_dir, color, piece)?;
+                let img = imageops::resize(&img, tile_size, tile_size, imageops::FilterType::Nearest);
+                pieces.push((color, piece, img));
+            }
+        }
+
+        Ok(BoardRenderer {
+            tile_size,
+            colors,
+            pieces,
+        })
+    }
+
+    /// Width of the frame holding the teaching coordinates, if they're drawn.
+    fn border(&self) -> u32 {
+        if self.colors.coordinates.is_some() { self.tile_size / 2 } else { 0 }
+    }
+
+    /// Width and height of the rendered images.
+    pub fn image_size(&self) -> u32 {
+        self.tile_size * 8 + self.border() * 2
+    }
+
+    pub fn render(&self, board: &Board, last_move: Option<Move>) -> RgbaImage {
+        let mut img = RgbaImage::new(self.image_size(), self.image_size());
+        for pixel in img.pixels_mut() {
+            pixel.0 = [255, 255, 255, 255];
+        }
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let color = if (x+y)%2==0 {
+                    self.colors.light
+                } else {
+                    self.colors.dark
+                };
+                self.blend_tile(&mut img, x, y, color);
+            }
+        }
+
+        if let Some(mv) = last_move {
+            for square in [mv.from, mv.to] {
+                let (x, y) = crate::index_to_coord(square as usize);
+                self.blend_tile(&mut img, x as u32, y as u32, self.colors.last_move);
+            }
+        }
+
+        for square in board.occupied() {
+            let (Some(piece), Some(color)) = (board.piece_on(square), board.color_on(square)) else {
+                continue;
+            };
+            let (x, y) = crate::index_to_coord(square as usize);
+            if let Some((_, _, piece_img)) = self.pieces.iter().find(|(c, p, _)| *c == color && *p == piece) {
+                let (left, top) = self.tile_origin(x as u32, y as u32);
+                imageops::overlay(&mut img, piece_img, left as i64, top as i64);
+            }
+        }
+
+        if let Some(color) = self.colors.coordinates {
+            self.draw_coordinates(&mut img, color);
+        }
+
+        img
+    }
+
+    fn tile_origin(&self, x: u32, y: u32) -> (u32, u32) {
+        (self.border() + x * self.tile_size, self.border() + y * self.tile_size)
+    }
+
+    /// Files and ranks in the frame, plus each square's name in its corner.
+    fn draw_coordinates(&self, img: &mut RgbaImage, color: Color) {
+        let border = self.border();
+        let big = (border / 10).max(1);
+        let small = (self.tile_size / 32).max(1);
+
+        for square in Square::ALL {
+            let (x, y) = crate::index_to_coord(square as usize);
+            let (left, top) = self.tile_origin(x as u32, y as u32);
+            let name = square.to_string();
+            draw_label(img, &name, left + small, top + self.tile_size - 8 * small, small, color);
+
+            if y == 7 {
+                draw_label(img, &name[..1], left + (self.tile_size - 5 * big) / 2, top + self.tile_size + (border - 7 * big) / 2, big, color);
+            }
+            if x == 0 {
+                draw_label(img, &name[1..], (border - 5 * big) / 2, top + (self.tile_size - 7 * big) / 2, big, color);
+            }
+        }
+    }
+
+    fn blend_tile(&self, img: &mut RgbaImage, x: u32, y: u32, color: Color) {
+        let [r, g, b, _] = color.into_rgba8();
+        let alpha = color.a;
+
+        let (left, top) = self.tile_origin(x, y);
+        for py in top..top + self.tile_size {
+            for px in left..left + self.tile_size {
+                let pixel = img.get_pixel_mut(px, py);
+                for (channel, value) in pixel.0.iter_mut().zip([r, g, b]) {
+                    *channel = (*channel as f32 * (1.0 - alpha) + value as f32 * alpha) as u8;
+                }
+                pixel.0[3] = 255;
+            }
+        }
+    }
+}
+
+/// Replays `moves` from `start` and writes every position as a frame of an animated GIF.
+pub fn export_gif(
+    path: &Path,
+    start: Board,
+    moves: &[Move],
+    renderer: &BoardRenderer,
+    delay_ms: u32,
+) -> Result<(), Box<dyn Error>> {
+    let size = renderer.image_size() as u16;
+    let mut encoder = gif::Encoder::new(File::create(path)?, size, size, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    let mut board = start;
+    let mut frames = vec![renderer.render(&board, None)];
+    for &mv in moves {
+        board.try_play(mv).map_err(|_| format!("illegal move in game: {mv}"))?;
+        frames.push(renderer.render(&board, Some(mv)));
+    }
+
+    for img in frames {
+        let mut pixels = img.into_raw();
+        let mut frame = gif::Frame::from_rgba_speed(size, size, &mut pixels, 10);
+        // gif delays are in hundredths of a second
+        frame.delay = (delay_ms / 10) as u16;
+        encoder.write_frame(&frame)?;
+    }
+
+    Ok(())
+}
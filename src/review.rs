@@ -0,0 +1,90 @@
+use std::thread;
+use std::time::Duration;
+
+use cozy_chess::{Board, Color, Move};
+use iced::futures::channel::oneshot;
+
+use crate::eval_graph;
+use crate::search;
+
+/// Depth every position is searched to when reviewing a game.
+const REVIEW_DEPTH: u32 = 3;
+// generous enough that the depth always decides when the search stops
+const REVIEW_TIME_LIMIT: Duration = Duration::from_secs(10);
+
+/// How much a move gave away compared to the search's choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Judgement {
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    /// Judges a move losing `loss` centipawns from the mover's point of view.
+    pub fn from_loss(loss: i32) -> Self {
+        match loss {
+            300.. => Judgement::Blunder,
+            100.. => Judgement::Mistake,
+            50.. => Judgement::Inaccuracy,
+            _ => Judgement::Good,
+        }
+    }
+
+    /// Annotation glyph written after the move, empty for good moves.
+    pub fn glyph(self) -> &'static str {
+        match self {
+            Judgement::Good => "",
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MoveReview {
+    pub judgement: Judgement,
+    /// What the search would have played instead, in the position before the move.
+    pub best_move: Option<Move>,
+}
+
+/// Searches every position of the game and judges each move by how much
+/// the evaluation dropped for the side that played it.
+pub fn review_game(start: &Board, moves: &[Move]) -> Vec<MoveReview> {
+    let mut board = start.clone();
+    let mut results = vec![search::search(&board, REVIEW_TIME_LIMIT, REVIEW_DEPTH)];
+    for &mv in moves {
+        board.play_unchecked(mv);
+        results.push(search::search(&board, REVIEW_TIME_LIMIT, REVIEW_DEPTH));
+    }
+
+    let mut board = start.clone();
+    moves.iter().zip(results.windows(2)).map(|(&mv, pair)| {
+        let before = eval_graph::graph_value(pair[0].score);
+        let after = eval_graph::graph_value(pair[1].score);
+        let loss = match board.side_to_move() {
+            Color::White => before - after,
+            Color::Black => after - before,
+        };
+        board.play_unchecked(mv);
+
+        let best_move = pair[0].best_move;
+        MoveReview {
+            judgement: if best_move == Some(mv) { Judgement::Good } else { Judgement::from_loss(loss) },
+            best_move,
+        }
+    }).collect()
+}
+
+/// Runs [`review_game`] on its own thread so the UI stays responsive.
+pub async fn review_in_background(start: Board, moves: Vec<Move>) -> Vec<MoveReview> {
+    let (sender, receiver) = oneshot::channel();
+
+    thread::spawn(move || {
+        let _ = sender.send(review_game(&start, &moves));
+    });
+
+    receiver.await.unwrap_or_default()
+}
@@ -0,0 +1,230 @@
+use cozy_chess::{BitBoard, Board, Color, File, GameStatus, Move, Piece, Rank};
+
+use crate::position;
+
+pub fn piece_letter(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn => 'P',
+        Piece::Knight => 'N',
+        Piece::Bishop => 'B',
+        Piece::Rook => 'R',
+        Piece::Queen => 'Q',
+        Piece::King => 'K',
+    }
+}
+
+fn file_char(file: File) -> char {
+    (b'a' + file as u8) as char
+}
+
+fn rank_char(rank: Rank) -> char {
+    (b'1' + rank as u8) as char
+}
+
+/// Standard algebraic notation of the legal move `mv` on `board`,
+/// e.g. `Nbd7`, `exd6`, `e8=Q+` or `O-O-O#`.
+pub fn to_san(board: &Board, mv: Move) -> String {
+    let us = board.side_to_move();
+    let piece = board.piece_on(mv.from).unwrap_or(Piece::Pawn);
+    let mut san = String::new();
+
+    if piece == Piece::King && board.colored_pieces(us, Piece::Rook).has(mv.to) {
+        san += if mv.to.file() as usize > mv.from.file() as usize { "O-O" } else { "O-O-O" };
+    } else {
+        let capture = position::is_capture(board, mv);
+
+        if piece == Piece::Pawn {
+            if capture {
+                san.push(file_char(mv.from.file()));
+            }
+        } else {
+            san.push(piece_letter(piece));
+
+            // other pieces of the same kind that could also land on the target
+            let mut rivals = BitBoard::EMPTY;
+            let others = board.colored_pieces(us, piece) & !mv.from.bitboard();
+            board.generate_moves_for(others, |moves| {
+                if moves.to.has(mv.to) {
+                    rivals |= moves.from.bitboard();
+                }
+                false
+            });
+
+            if !rivals.is_empty() {
+                let shares_file = rivals.iter().any(|square| square.file() == mv.from.file());
+                let shares_rank = rivals.iter().any(|square| square.rank() == mv.from.rank());
+                if !shares_file {
+                    san.push(file_char(mv.from.file()));
+                } else if !shares_rank {
+                    san.push(rank_char(mv.from.rank()));
+                } else {
+                    san.push(file_char(mv.from.file()));
+                    san.push(rank_char(mv.from.rank()));
+                }
+            }
+        }
+
+        if capture {
+            san.push('x');
+        }
+        san.push(file_char(mv.to.file()));
+        san.push(rank_char(mv.to.rank()));
+
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push(piece_letter(promotion));
+        }
+    }
+
+    let mut after = board.clone();
+    after.play_unchecked(mv);
+    if after.status() == GameStatus::Won {
+        san.push('#');
+    } else if !after.checkers().is_empty() {
+        san.push('+');
+    }
+
+    san
+}
+
+/// Numbered movetext such as `1. e4 e5 2. Nf3`, replayed from `start`.
+/// A game starting with Black to move begins with `1...`.
+pub fn move_list_text(start: &Board, moves: &[Move]) -> String {
+    annotated_move_list_text(start, moves, |_| None)
+}
+
+/// Movetext like [`move_list_text`], with `comment(index)` written in braces
+/// after every move that has one.
+pub fn annotated_move_list_text(start: &Board, moves: &[Move], comment: impl Fn(usize) -> Option<String>) -> String {
+    let mut board = start.clone();
+    let mut text = String::new();
+    let mut interrupted = true;
+
+    for (index, &mv) in moves.iter().enumerate() {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+
+        // a Black move needs its number again after a comment
+        match board.side_to_move() {
+            Color::White => text += &format!("{}. ", board.fullmove_number()),
+            Color::Black if interrupted => text += &format!("{}... ", board.fullmove_number()),
+            Color::Black => {},
+        }
+
+        text += &to_san(&board, mv);
+        board.play_unchecked(mv);
+
+        interrupted = false;
+        if let Some(comment) = comment(index) {
+            text += &format!(" {{{comment}}}");
+            interrupted = true;
+        }
+    }
+
+    text
+}
+
+/// The legal move on `board` written as `san`. Check marks are optional and
+/// castling may be written with zeros.
+pub fn parse_san(board: &Board, san: &str) -> Option<Move> {
+    let wanted = san.trim_end_matches(['+', '#']).replace('0', "O");
+
+    let mut found = None;
+    board.generate_moves(|moves| {
+        for mv in moves {
+            if to_san(board, mv).trim_end_matches(['+', '#']) == wanted {
+                found = Some(mv);
+                return true;
+            }
+        }
+        false
+    });
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen, false).unwrap()
+    }
+
+    fn mv(uci: &str) -> Move {
+        uci.parse().unwrap()
+    }
+
+    /// The position after playing `moves`, in UCI, from the start.
+    fn after(moves: &[&str]) -> Board {
+        let mut board = Board::default();
+        for &uci in moves {
+            board.play(mv(uci));
+        }
+        board
+    }
+
+    #[test]
+    fn disambiguates_by_file_then_rank() {
+        let by_file = board("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1");
+        assert_eq!(to_san(&by_file, mv("b1d2")), "Nbd2");
+        assert_eq!(to_san(&by_file, mv("f3d2")), "Nfd2");
+
+        let by_rank = board("4k3/8/8/8/8/1N6/8/1N2K3 w - - 0 1");
+        assert_eq!(to_san(&by_rank, mv("b1d2")), "N1d2");
+        assert_eq!(to_san(&by_rank, mv("b3d2")), "N3d2");
+        assert_eq!(parse_san(&by_rank, "N3d2"), Some(mv("b3d2")));
+    }
+
+    #[test]
+    fn writes_en_passant_as_a_pawn_capture() {
+        let board = board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        assert_eq!(to_san(&board, mv("e5d6")), "exd6");
+        assert_eq!(parse_san(&board, "exd6"), Some(mv("e5d6")));
+    }
+
+    #[test]
+    fn writes_promotions_with_the_piece() {
+        let board = board("k7/4P3/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(to_san(&board, mv("e7e8q")), "e8=Q+");
+        assert_eq!(to_san(&board, mv("e7e8n")), "e8=N");
+        assert_eq!(parse_san(&board, "e8=Q"), Some(mv("e7e8q")));
+    }
+
+    #[test]
+    fn writes_castling_from_king_takes_rook() {
+        let board = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(to_san(&board, mv("e1h1")), "O-O");
+        assert_eq!(to_san(&board, mv("e1a1")), "O-O-O");
+        assert_eq!(parse_san(&board, "O-O"), Some(mv("e1h1")));
+        assert_eq!(parse_san(&board, "0-0-0"), Some(mv("e1a1")));
+    }
+
+    #[test]
+    fn marks_check_and_mate() {
+        let check = after(&["e2e4", "f7f6"]);
+        assert_eq!(to_san(&check, mv("d1h5")), "Qh5+");
+
+        let mate = after(&["e2e4", "e7e5", "d1h5", "b8c6", "f1c4", "g8f6"]);
+        assert_eq!(to_san(&mate, mv("h5f7")), "Qxf7#");
+        assert_eq!(parse_san(&mate, "Qxf7"), Some(mv("h5f7")));
+    }
+
+    #[test]
+    fn parses_every_move_it_writes() {
+        let positions = [
+            Board::default(),
+            board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"),
+            board("4k3/8/8/8/8/1N6/8/1N2K3 w - - 0 1"),
+            after(&["e2e4", "e7e5", "d1h5", "b8c6", "f1c4", "g8f6"]),
+        ];
+        for board in positions {
+            board.generate_moves(|moves| {
+                for mv in moves {
+                    assert_eq!(parse_san(&board, &to_san(&board, mv)), Some(mv));
+                }
+                false
+            });
+        }
+    }
+}
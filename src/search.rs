@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cozy_chess::{Board, Color, Move, Piece};
+use iced::futures::channel::oneshot;
+
+use crate::eval::{self, Score};
+use crate::position;
+
+const MATE: i32 = 30_000;
+// anything above this is a forced mate rather than a material score
+const MATE_BOUND: i32 = MATE - 1_000;
+pub const MAX_DEPTH: u32 = 64;
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// From White's point of view.
+    pub score: Score,
+    /// Depth of the last fully completed iteration.
+    pub depth: u32,
+    pub nodes: u64,
+    /// Line expected from the position, starting with `best_move`.
+    pub pv: Vec<Move>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TtEntry {
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// Iterative deepening negamax with alpha-beta pruning and a transposition
+/// table shared between iterations.
+struct Searcher<'a> {
+    tt: HashMap<u64, TtEntry>,
+    nodes: u64,
+    deadline: Instant,
+    /// Set from another thread to give up before the deadline.
+    stop: &'a AtomicBool,
+    out_of_time: bool,
+}
+
+/// Searches deeper and deeper until `time_limit` runs out, answering with the
+/// best move of the last iteration that finished.
+pub fn search(board: &Board, time_limit: Duration, max_depth: u32) -> SearchResult {
+    search_until_stopped(board, time_limit, max_depth, &AtomicBool::new(false))
+}
+
+/// Like [`search`], also giving up as soon as `stop` is set.
+pub fn search_until_stopped(board: &Board, time_limit: Duration, max_depth: u32, stop: &AtomicBool) -> SearchResult {
+    let mut searcher = Searcher {
+        tt: HashMap::new(),
+        nodes: 0,
+        deadline: Instant::now() + time_limit,
+        stop,
+        out_of_time: false,
+    };
+
+    let mut result = SearchResult {
+        best_move: None,
+        score: eval::evaluate(board),
+        depth: 0,
+        nodes: 0,
+        pv: Vec::new(),
+    };
+
+    for depth in 1..=max_depth {
+        let score = searcher.negamax(board, depth, 0, -MATE, MATE);
+        if searcher.out_of_time {
+            break;
+        }
+
+        result = SearchResult {
+            best_move: searcher.tt.get(&board.hash()).and_then(|entry| entry.best_move),
+            score: to_white_score(score, board.side_to_move()),
+            depth,
+            nodes: searcher.nodes,
+            pv: searcher.principal_variation(board, depth),
+        };
+
+        // no point looking deeper once a forced mate has been found
+        if score.abs() > MATE_BOUND {
+            break;
+        }
+    }
+
+    result.nodes = searcher.nodes;
+    result
+}
+
+/// The capture winning the most material for the side to move, looking a
+/// single ply ahead: taking a defended piece is assumed to lose the taker.
+/// Returns the move with what it wins in centipawns, if anything.
+pub fn best_capture(board: &Board) -> Option<(Move, i32)> {
+    let mut best: Option<(Move, i32)> = None;
+    for mv in ordered_moves(board, None) {
+        if !position::is_capture(board, mv) {
+            continue;
+        }
+
+        let taken = board.piece_on(mv.to).unwrap_or(Piece::Pawn);
+        let mut after = board.clone();
+        after.play_unchecked(mv);
+        let defended = position::attacked_squares(&after, after.side_to_move()).has(mv.to);
+        let taker = board.piece_on(mv.from).map_or(0, eval::piece_value);
+        let gain = eval::piece_value(taken) - if defended { taker } else { 0 };
+
+        if gain > 0 && best.is_none_or(|(_, best_gain)| gain > best_gain) {
+            best = Some((mv, gain));
+        }
+    }
+    best
+}
+
+/// How forced the side to move is, for checking that a puzzle has a single solution.
+#[derive(Debug, Clone)]
+pub struct OnlyMove {
+    pub legal_moves: u32,
+    pub best_move: Option<Move>,
+    /// Centipawns between the best and the second best move, from the
+    /// mover's side. `None` with fewer than two legal moves.
+    pub margin: Option<i32>,
+}
+
+impl OnlyMove {
+    /// Best move by at least this much to count as the only move.
+    pub const MARGIN: i32 = 200;
+
+    pub fn is_forced(&self) -> bool {
+        self.legal_moves == 1 || self.margin.is_some_and(|margin| margin >= Self::MARGIN)
+    }
+}
+
+/// Scores every legal move with a full-width search `depth` plies deep to
+/// find out whether one stands out from the rest.
+pub fn only_move(board: &Board, depth: u32) -> OnlyMove {
+    let stop = AtomicBool::new(false);
+    let mut searcher = Searcher {
+        tt: HashMap::new(),
+        nodes: 0,
+        // bounded by depth alone
+        deadline: Instant::now() + Duration::from_secs(60 * 60),
+        stop: &stop,
+        out_of_time: false,
+    };
+
+    let mut scored: Vec<(i32, Move)> = ordered_moves(board, None).into_iter()
+        .map(|mv| {
+            let mut child = board.clone();
+            child.play_unchecked(mv);
+            (-searcher.negamax(&child, depth.saturating_sub(1), 1, -MATE, MATE), mv)
+        })
+        .collect();
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+
+    OnlyMove {
+        legal_moves: scored.len() as u32,
+        best_move: scored.first().map(|&(_, mv)| mv),
+        margin: match scored.as_slice() {
+            [(best, _), (second, _), ..] => Some(best - second),
+            _ => None,
+        },
+    }
+}
+
+/// Runs [`only_move`] on its own thread so the UI stays responsive.
+pub async fn only_move_in_background(board: Board, depth: u32) -> OnlyMove {
+    let (sender, receiver) = oneshot::channel();
+    let legal_moves = position::count_legal_moves(&board);
+
+    thread::spawn(move || {
+        let _ = sender.send(only_move(&board, depth));
+    });
+
+    receiver.await.unwrap_or(OnlyMove { legal_moves, best_move: None, margin: None })
+}
+
+/// Runs [`search_until_stopped`] on its own thread so the UI stays responsive.
+pub async fn search_in_background(board: Board, time_limit: Duration, max_depth: u32, stop: Arc<AtomicBool>) -> SearchResult {
+    let (sender, receiver) = oneshot::channel();
+    let fallback = board.clone();
+
+    thread::spawn(move || {
+        let _ = sender.send(search_until_stopped(&board, time_limit, max_depth, &stop));
+    });
+
+    receiver.await.unwrap_or_else(|_| SearchResult {
+        best_move: None,
+        score: eval::evaluate(&fallback),
+        depth: 0,
+        nodes: 0,
+        pv: Vec::new(),
+    })
+}
+
+impl Searcher<'_> {
+    /// Best moves stored in the table from `board` on, at most `depth` of them.
+    fn principal_variation(&self, board: &Board, depth: u32) -> Vec<Move> {
+        let mut board = board.clone();
+        let mut pv = Vec::new();
+        while pv.len() < depth as usize {
+            let Some(mv) = self.tt.get(&board.hash()).and_then(|entry| entry.best_move) else {
+                break;
+            };
+            // an entry of another position sharing the hash
+            if !board.is_legal(mv) {
+                break;
+            }
+            board.play_unchecked(mv);
+            pv.push(mv);
+        }
+        pv
+    }
+
+    fn negamax(&mut self, board: &Board, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes % 1024 == 0 && (Instant::now() >= self.deadline || self.stop.load(Ordering::Relaxed)) {
+            self.out_of_time = true;
+        }
+        if self.out_of_time {
+            return 0;
+        }
+
+        let moves = ordered_moves(board, self.tt.get(&board.hash()).and_then(|entry| entry.best_move));
+        if moves.is_empty() {
+            return if board.checkers().is_empty() { 0 } else { -MATE + ply };
+        }
+        if board.halfmove_clock() >= 100 {
+            return 0;
+        }
+
+        if let Some(entry) = self.tt.get(&board.hash()) {
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        if depth == 0 {
+            return match board.side_to_move() {
+                Color::White => eval::static_eval(board),
+                Color::Black => -eval::static_eval(board),
+            };
+        }
+
+        let original_alpha = alpha;
+        let mut best_score = -MATE;
+        let mut best_move = None;
+
+        for mv in moves {
+            let mut child = board.clone();
+            child.play_unchecked(mv);
+
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha);
+            if self.out_of_time {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= original_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.tt.insert(board.hash(), TtEntry {
+            depth,
+            score: score_to_tt(best_score, ply),
+            bound,
+            best_move,
+        });
+
+        best_score
+    }
+}
+
+/// Legal moves with the remembered best move first, then captures of the
+/// most valuable pieces.
+fn ordered_moves(board: &Board, tt_move: Option<Move>) -> Vec<Move> {
+    let mut moves = Vec::new();
+    board.generate_moves(|piece_moves| {
+        moves.extend(piece_moves);
+        false
+    });
+
+    let them = board.colors(!board.side_to_move());
+    moves.sort_by_key(|&mv| {
+        if Some(mv) == tt_move {
+            i32::MIN
+        } else if them.has(mv.to) {
+            -board.piece_on(mv.to).map_or(0, eval::piece_value)
+        } else {
+            0
+        }
+    });
+    moves
+}
+
+// mate scores are stored relative to the node so they stay valid when the
+// same position is reached at a different ply
+fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score > MATE_BOUND {
+        score + ply
+    } else if score < -MATE_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score > MATE_BOUND {
+        score - ply
+    } else if score < -MATE_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
+
+fn to_white_score(score: i32, side_to_move: Color) -> Score {
+    let winner = if score > 0 { side_to_move } else { !side_to_move };
+
+    if score.abs() > MATE_BOUND {
+        let plies = (MATE - score.abs()) as u32;
+        Score::Mate(winner, plies.div_ceil(2))
+    } else {
+        match side_to_move {
+            Color::White => Score::Centipawns(score),
+            Color::Black => Score::Centipawns(-score),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::io;
+
+use cozy_chess::{Board, Color, Move};
+
+use crate::game_end::GameEnd;
+
+const SESSION_PATH: &str = "session.cfg";
+/// Bumped whenever the meaning of a key changes, so older files are ignored
+/// instead of being misread.
+const SESSION_VERSION: u32 = 2;
+
+/// The game and view state restored on the next launch, stored as
+/// `key = value` lines like the settings.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub start: Board,
+    pub moves: Vec<Move>,
+    /// Variation being explored, as (branch ply, moves).
+    pub variation: Option<(usize, Vec<Move>)>,
+    pub game_end: Option<GameEnd>,
+    pub flipped: bool,
+    pub vs_bot: bool,
+    pub bot_color: Color,
+}
+
+impl Session {
+    /// The saved session, or `None` when there is none or it can't be used.
+    pub fn load() -> Option<Self> {
+        let contents = fs::read_to_string(SESSION_PATH).ok()?;
+        let entries: Vec<(&str, &str)> = contents.lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+        let get = |key: &str| entries.iter().find(|(k, _)| *k == key).map(|(_, value)| *value);
+
+        if get("version")?.parse::<u32>().ok()? != SESSION_VERSION {
+            return None;
+        }
+
+        let fen = get("start")?;
+        let start = Board::from_fen(fen, false).or_else(|_| Board::from_fen(fen, true)).ok()?;
+        let moves = parse_moves(&start, get("moves").unwrap_or(""))?;
+
+        let mut branch = start.clone();
+        let variation = match get("variation_ply") {
+            Some(ply) => {
+                let ply: usize = ply.parse().ok()?;
+                for &mv in moves.get(..ply)? {
+                    branch.play_unchecked(mv);
+                }
+                Some((ply, parse_moves(&branch, get("variation_moves").unwrap_or(""))?))
+            },
+            None => None,
+        };
+
+        Some(Session {
+            start,
+            moves,
+            variation,
+            game_end: match get("game_end") {
+                Some(key) => Some(GameEnd::from_key(key)?),
+                None => None,
+            },
+            flipped: get("flipped")?.parse().ok()?,
+            vs_bot: get("vs_bot")?.parse().ok()?,
+            bot_color: match get("bot_color")? {
+                "white" => Color::White,
+                "black" => Color::Black,
+                _ => return None,
+            },
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut entries = vec![
+            ("version", SESSION_VERSION.to_string()),
+            ("start", self.start.to_string()),
+            ("moves", moves_to_string(&self.moves)),
+            ("flipped", self.flipped.to_string()),
+            ("vs_bot", self.vs_bot.to_string()),
+            ("bot_color", match self.bot_color {
+                Color::White => "white".to_owned(),
+                Color::Black => "black".to_owned(),
+            }),
+        ];
+        if let Some((ply, moves)) = &self.variation {
+            entries.push(("variation_ply", ply.to_string()));
+            entries.push(("variation_moves", moves_to_string(moves)));
+        }
+        if let Some(end) = &self.game_end {
+            entries.push(("game_end", end.to_key()));
+        }
+
+        let mut contents = String::new();
+        for (key, value) in entries {
+            contents += &format!("{key} = {value}\n");
+        }
+        fs::write(SESSION_PATH, contents)
+    }
+}
+
+fn moves_to_string(moves: &[Move]) -> String {
+    moves.iter().map(Move::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// Replays space separated moves from `start`, rejecting illegal ones.
+fn parse_moves(start: &Board, moves: &str) -> Option<Vec<Move>> {
+    let mut board = start.clone();
+    let mut parsed = Vec::new();
+    for mv in moves.split_whitespace() {
+        let mv: Move = mv.parse().ok()?;
+        board.try_play(mv).ok()?;
+        parsed.push(mv);
+    }
+    Some(parsed)
+}
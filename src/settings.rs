@@ -0,0 +1,841 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use cozy_chess::Piece;
+use iced::Color;
+
+const SETTINGS_PATH: &str = "settings.cfg";
+
+/// Every color drawn on top of the board squares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighlightColors {
+    pub selection: Color,
+    pub selected_outline: Color,
+    pub illegal_hint: Color,
+    pub last_move: Color,
+    pub check: Color,
+    pub wrong_move: Color,
+    pub arrow: Color,
+    pub castling_marker: Color,
+    pub threat: Color,
+    pub promotion_backdrop: Color,
+    pub promotion_hover: Color,
+    /// Soft fill of the square under the mouse.
+    pub hover: Color,
+    /// Outline of the square under the keyboard cursor.
+    pub keyboard_cursor: Color,
+    /// Tint of the pieces the selected piece defends.
+    pub defended: Color,
+    /// Squares of the queued premove.
+    pub premove: Color,
+    /// Target square while dragging, when the drop is legal.
+    pub drop_legal: Color,
+    /// Target square while dragging, when the drop is illegal.
+    pub drop_illegal: Color,
+    /// Faint arrows showing every legal move.
+    pub ghost_arrow: Color,
+    /// Arrow warning that a move hangs material.
+    pub blunder_arrow: Color,
+    /// Arrow showing the better move in an annotated game.
+    pub best_move_arrow: Color,
+    /// The `+` marking squares the selected piece gives check from.
+    pub check_mark: Color,
+    /// Squares of the bitboard debug overlay, and the debug text drawn opaque.
+    pub debug_overlay: Color,
+    /// Background of the hovered square's name next to the cursor.
+    pub cursor_label: Color,
+}
+
+impl Default for HighlightColors {
+    fn default() -> Self {
+        HighlightPreset::Classic.colors()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightPreset {
+    Classic,
+    HighContrast,
+    Muted,
+}
+
+impl HighlightPreset {
+    pub const ALL: [HighlightPreset; 3] = [
+        HighlightPreset::Classic,
+        HighlightPreset::HighContrast,
+        HighlightPreset::Muted,
+    ];
+
+    pub fn colors(self) -> HighlightColors {
+        match self {
+            HighlightPreset::Classic => HighlightColors {
+                selection: Color::from_rgba(0.0, 0.0, 1.0, 0.5),
+                selected_outline: Color::from_rgb(0.0, 0.0, 1.0),
+                illegal_hint: Color::from_rgba(1.0, 0.0, 0.0, 0.15),
+                last_move: Color::from_rgba(1.0, 1.0, 0.0, 0.35),
+                check: Color::from_rgba(1.0, 0.0, 0.0, 0.5),
+                wrong_move: Color::from_rgba(1.0, 0.0, 0.0, 0.6),
+                arrow: Color::from_rgba(0.0, 0.6, 0.0, 0.7),
+                castling_marker: Color::from_rgb(1.0, 0.8, 0.0),
+                threat: Color::from_rgba(1.0, 0.4, 0.0, 0.2),
+                promotion_backdrop: Color::from_rgba(0.0, 0.0, 0.0, 0.9),
+                promotion_hover: Color::from_rgba(0.0, 1.0, 0.0, 0.5),
+                hover: Color::from_rgba(1.0, 1.0, 1.0, 0.2),
+                keyboard_cursor: Color::from_rgb(1.0, 0.5, 0.0),
+                defended: Color::from_rgba(0.2, 0.5, 1.0, 0.35),
+                premove: Color::from_rgba(0.9, 0.3, 0.1, 0.35),
+                drop_legal: Color::from_rgba(0.1, 0.8, 0.2, 0.4),
+                drop_illegal: Color::from_rgba(0.9, 0.1, 0.1, 0.4),
+                ghost_arrow: Color::from_rgba(0.2, 0.4, 1.0, 0.25),
+                blunder_arrow: Color::from_rgba(0.9, 0.1, 0.1, 0.7),
+                best_move_arrow: Color::from_rgba(0.1, 0.7, 0.3, 0.7),
+                check_mark: Color::from_rgb(0.85, 0.1, 0.1),
+                debug_overlay: Color::from_rgba(1.0, 0.0, 1.0, 0.4),
+                cursor_label: Color::from_rgba(0.0, 0.0, 0.0, 0.7),
+            },
+            HighlightPreset::HighContrast => HighlightColors {
+                selection: Color::from_rgba(0.0, 0.6, 1.0, 0.7),
+                selected_outline: Color::from_rgb(0.0, 0.9, 1.0),
+                illegal_hint: Color::from_rgba(1.0, 0.0, 0.0, 0.3),
+                last_move: Color::from_rgba(1.0, 0.6, 0.0, 0.6),
+                check: Color::from_rgba(1.0, 0.0, 0.0, 0.8),
+                wrong_move: Color::from_rgba(1.0, 0.0, 0.0, 0.9),
+                arrow: Color::from_rgba(0.0, 0.8, 0.0, 0.9),
+                castling_marker: Color::from_rgb(1.0, 0.9, 0.0),
+                threat: Color::from_rgba(1.0, 0.3, 0.0, 0.4),
+                promotion_backdrop: Color::from_rgba(0.0, 0.0, 0.0, 0.95),
+                promotion_hover: Color::from_rgba(0.0, 1.0, 0.0, 0.8),
+                hover: Color::from_rgba(1.0, 1.0, 1.0, 0.35),
+                keyboard_cursor: Color::from_rgb(1.0, 0.0, 1.0),
+                defended: Color::from_rgba(0.0, 0.6, 1.0, 0.55),
+                premove: Color::from_rgba(1.0, 0.3, 0.0, 0.6),
+                drop_legal: Color::from_rgba(0.0, 1.0, 0.2, 0.6),
+                drop_illegal: Color::from_rgba(1.0, 0.0, 0.0, 0.6),
+                ghost_arrow: Color::from_rgba(0.0, 0.5, 1.0, 0.45),
+                blunder_arrow: Color::from_rgba(1.0, 0.0, 0.0, 0.9),
+                best_move_arrow: Color::from_rgba(0.0, 0.9, 0.3, 0.9),
+                check_mark: Color::from_rgb(1.0, 0.0, 0.0),
+                debug_overlay: Color::from_rgba(1.0, 0.0, 1.0, 0.6),
+                cursor_label: Color::from_rgba(0.0, 0.0, 0.0, 0.9),
+            },
+            HighlightPreset::Muted => HighlightColors {
+                selection: Color::from_rgba(0.2, 0.3, 0.5, 0.35),
+                selected_outline: Color::from_rgb(0.3, 0.4, 0.6),
+                illegal_hint: Color::from_rgba(0.6, 0.2, 0.2, 0.12),
+                last_move: Color::from_rgba(0.6, 0.6, 0.3, 0.25),
+                check: Color::from_rgba(0.7, 0.2, 0.2, 0.35),
+                wrong_move: Color::from_rgba(0.7, 0.2, 0.2, 0.5),
+                arrow: Color::from_rgba(0.3, 0.5, 0.3, 0.6),
+                castling_marker: Color::from_rgb(0.7, 0.6, 0.3),
+                threat: Color::from_rgba(0.6, 0.4, 0.2, 0.15),
+                promotion_backdrop: Color::from_rgba(0.0, 0.0, 0.0, 0.8),
+                promotion_hover: Color::from_rgba(0.3, 0.6, 0.3, 0.4),
+                hover: Color::from_rgba(1.0, 1.0, 1.0, 0.12),
+                keyboard_cursor: Color::from_rgb(0.6, 0.5, 0.3),
+                defended: Color::from_rgba(0.3, 0.4, 0.6, 0.25),
+                premove: Color::from_rgba(0.6, 0.4, 0.3, 0.25),
+                drop_legal: Color::from_rgba(0.3, 0.6, 0.3, 0.3),
+                drop_illegal: Color::from_rgba(0.6, 0.3, 0.3, 0.3),
+                ghost_arrow: Color::from_rgba(0.3, 0.4, 0.6, 0.2),
+                blunder_arrow: Color::from_rgba(0.7, 0.2, 0.2, 0.5),
+                best_move_arrow: Color::from_rgba(0.3, 0.5, 0.4, 0.5),
+                check_mark: Color::from_rgb(0.6, 0.25, 0.25),
+                debug_overlay: Color::from_rgba(0.6, 0.3, 0.6, 0.35),
+                cursor_label: Color::from_rgba(0.1, 0.1, 0.1, 0.55),
+            },
+        }
+    }
+}
+
+impl fmt::Display for HighlightPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HighlightPreset::Classic => "Classic",
+            HighlightPreset::HighContrast => "High contrast",
+            HighlightPreset::Muted => "Muted",
+        })
+    }
+}
+
+/// How the previous move is marked on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LastMoveStyle {
+    Squares,
+    Arrow,
+    Both,
+}
+
+impl LastMoveStyle {
+    pub const ALL: [LastMoveStyle; 3] = [LastMoveStyle::Squares, LastMoveStyle::Arrow, LastMoveStyle::Both];
+
+    pub fn squares(self) -> bool {
+        self != LastMoveStyle::Arrow
+    }
+
+    pub fn arrow(self) -> bool {
+        self != LastMoveStyle::Squares
+    }
+}
+
+impl fmt::Display for LastMoveStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LastMoveStyle::Squares => "Squares",
+            LastMoveStyle::Arrow => "Arrow",
+            LastMoveStyle::Both => "Both",
+        })
+    }
+}
+
+impl FromStr for LastMoveStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LastMoveStyle::ALL.into_iter().find(|style| style.to_string() == s).ok_or(())
+    }
+}
+
+/// Which pieces a move overlay applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PieceFilter {
+    All,
+    Only(Piece),
+}
+
+impl PieceFilter {
+    pub const ALL: [PieceFilter; 7] = [
+        PieceFilter::All,
+        PieceFilter::Only(Piece::Pawn),
+        PieceFilter::Only(Piece::Knight),
+        PieceFilter::Only(Piece::Bishop),
+        PieceFilter::Only(Piece::Rook),
+        PieceFilter::Only(Piece::Queen),
+        PieceFilter::Only(Piece::King),
+    ];
+
+    pub fn allows(self, piece: Piece) -> bool {
+        self == PieceFilter::All || self == PieceFilter::Only(piece)
+    }
+}
+
+impl fmt::Display for PieceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PieceFilter::All => "All pieces",
+            PieceFilter::Only(Piece::Pawn) => "Pawns",
+            PieceFilter::Only(Piece::Knight) => "Knights",
+            PieceFilter::Only(Piece::Bishop) => "Bishops",
+            PieceFilter::Only(Piece::Rook) => "Rooks",
+            PieceFilter::Only(Piece::Queen) => "Queens",
+            PieceFilter::Only(Piece::King) => "Kings",
+        })
+    }
+}
+
+impl FromStr for PieceFilter {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PieceFilter::ALL.into_iter().find(|filter| filter.to_string() == s).ok_or(())
+    }
+}
+
+/// How pieces are moved with the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    /// Click the piece, then click where it goes.
+    ClickClick,
+    Drag,
+    /// Either, told apart by how far and how long the mouse moved while pressed.
+    Both,
+}
+
+impl InputMode {
+    pub const ALL: [InputMode; 3] = [InputMode::ClickClick, InputMode::Drag, InputMode::Both];
+}
+
+impl fmt::Display for InputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InputMode::ClickClick => "Click",
+            InputMode::Drag => "Drag",
+            InputMode::Both => "Click or drag",
+        })
+    }
+}
+
+impl FromStr for InputMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        InputMode::ALL.into_iter().find(|mode| mode.to_string() == s).ok_or(())
+    }
+}
+
+/// Which moves make a sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveSounds {
+    Always,
+    /// Only the bot's or engine's moves, to notice it's your turn again.
+    OpponentOnly,
+    Never,
+}
+
+impl MoveSounds {
+    pub const ALL: [MoveSounds; 3] = [MoveSounds::Always, MoveSounds::OpponentOnly, MoveSounds::Never];
+}
+
+impl fmt::Display for MoveSounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MoveSounds::Always => "All moves",
+            MoveSounds::OpponentOnly => "Opponent's moves",
+            MoveSounds::Never => "No moves",
+        })
+    }
+}
+
+impl FromStr for MoveSounds {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MoveSounds::ALL.into_iter().find(|sounds| sounds.to_string() == s).ok_or(())
+    }
+}
+
+/// What happens when a pawn reaches the last rank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromotionBehavior {
+    /// Open the picker every time.
+    Ask,
+    AlwaysQueen,
+    /// Promote to the piece picked last time.
+    RememberLast,
+}
+
+impl PromotionBehavior {
+    pub const ALL: [PromotionBehavior; 3] = [
+        PromotionBehavior::Ask,
+        PromotionBehavior::AlwaysQueen,
+        PromotionBehavior::RememberLast,
+    ];
+}
+
+impl fmt::Display for PromotionBehavior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PromotionBehavior::Ask => "Always ask",
+            PromotionBehavior::AlwaysQueen => "Always queen",
+            PromotionBehavior::RememberLast => "Remember last choice",
+        })
+    }
+}
+
+impl FromStr for PromotionBehavior {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PromotionBehavior::ALL.into_iter().find(|behavior| behavior.to_string() == s).ok_or(())
+    }
+}
+
+/// Who plays against the user in a new game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opponent {
+    Human,
+    Bot,
+    /// The loaded UCI engine.
+    Engine,
+}
+
+impl Opponent {
+    pub const ALL: [Opponent; 3] = [Opponent::Human, Opponent::Bot, Opponent::Engine];
+}
+
+impl fmt::Display for Opponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Opponent::Human => "Human",
+            Opponent::Bot => "Bot",
+            Opponent::Engine => "Engine",
+        })
+    }
+}
+
+impl FromStr for Opponent {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Opponent::ALL.into_iter().find(|opponent| opponent.to_string() == s).ok_or(())
+    }
+}
+
+/// How strictly a game is played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// Moves can be taken back, for practice and study.
+    Casual,
+    /// Every move stands, as in a timed competitive game.
+    Serious,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 2] = [GameMode::Casual, GameMode::Serious];
+
+    pub fn allow_takeback(self) -> bool {
+        self == GameMode::Casual
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GameMode::Casual => "Casual",
+            GameMode::Serious => "Serious",
+        })
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GameMode::ALL.into_iter().find(|mode| mode.to_string() == s).ok_or(())
+    }
+}
+
+pub const STANDARD_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// A named position new games can start from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionPreset {
+    pub name: String,
+    pub fen: String,
+}
+
+impl PositionPreset {
+    fn new(name: &str, fen: &str) -> Self {
+        PositionPreset { name: name.to_owned(), fen: fen.to_owned() }
+    }
+
+    /// Presets that ship with the app, listed before the saved ones.
+    pub fn built_in() -> Vec<PositionPreset> {
+        vec![
+            PositionPreset::new("Standard start", STANDARD_FEN),
+            // a position needs both kings, so this is as empty as it gets
+            PositionPreset::new("Empty board (kings only)", "4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            PositionPreset::new("Lucena position", "1K1k4/1P6/8/8/8/8/r7/2R5 w - - 0 1"),
+            PositionPreset::new("Philidor position", "4k3/R7/7r/3KP3/8/8/8/8 b - - 0 1"),
+        ]
+    }
+}
+
+impl fmt::Display for PositionPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Choices of the New Game dialog, remembered for the next game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSetup {
+    /// Base time and increment, or `None` to play without a clock.
+    pub clock: Option<(Duration, Duration)>,
+    pub human_color: cozy_chess::Color,
+    pub opponent: Opponent,
+    /// FEN of the position the game starts from.
+    pub start_fen: String,
+    pub mode: GameMode,
+}
+
+impl Default for GameSetup {
+    fn default() -> Self {
+        GameSetup {
+            clock: None,
+            human_color: cozy_chess::Color::White,
+            opponent: Opponent::Human,
+            start_fen: STANDARD_FEN.to_owned(),
+            mode: GameMode::Casual,
+        }
+    }
+}
+
+/// User preferences that survive restarts, stored as `key = value` lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub highlight_colors: HighlightColors,
+    pub show_eval_number: bool,
+    /// Spell out which pieces each side is up or down when material is uneven.
+    pub show_imbalance: bool,
+    /// Wall-clock budget for each analysis search.
+    pub search_time_ms: u32,
+    /// Let the bot open with book moves instead of searching.
+    pub use_opening_book: bool,
+    /// Show why some squares are unavailable to the selected piece.
+    pub learner_hints: bool,
+    /// Only mark the selected piece's captures, for spotting tactics.
+    pub captures_only: bool,
+    pub input_mode: InputMode,
+    pub promotion_behavior: PromotionBehavior,
+    /// Piece chosen in the promotion picker last time.
+    pub last_promotion: Piece,
+    /// Mark the selected piece's moves that give check.
+    pub show_checking_moves: bool,
+    /// Tint the friendly pieces the selected piece defends.
+    pub show_defended: bool,
+    pub last_move_style: LastMoveStyle,
+    /// Play transitions at all, and how fast relative to normal.
+    pub animations: bool,
+    pub animation_speed: f32,
+    /// Draw the latest moves as arrows fading with age.
+    pub move_trail: bool,
+    pub move_trail_length: u32,
+    /// Share of the window width given to the board, the panel gets the rest.
+    pub panel_split: f32,
+    /// Space around the board, where the coordinates are written.
+    pub board_margin: f32,
+    pub sound_enabled: bool,
+    pub move_sounds: MoveSounds,
+    pub show_castling_markers: bool,
+    /// Label the square under the mouse, for streams and lessons.
+    pub show_cursor_square: bool,
+    /// Large border labels and a name in every square, for lesson screenshots
+    /// and image exports.
+    pub teaching_coordinates: bool,
+    pub coordinate_color: Color,
+    /// Draw every legal move of the side to move as a faint arrow.
+    pub ghost_arrows: bool,
+    pub ghost_arrow_filter: PieceFilter,
+    /// Tint what the opponent would attack after the hovered move.
+    pub threat_preview: bool,
+    /// Warn with an arrow when the opponent can win material after my move.
+    pub blunder_warning: bool,
+    /// Material, in centipawns, a capture must win to be warned about.
+    pub blunder_threshold: u32,
+    /// Write FENs with file letters for castling rights, as Chess960 needs.
+    pub shredder_fen: bool,
+    /// Write `%clk` comments when saving a game as PGN.
+    pub pgn_clock_annotations: bool,
+    pub game_setup: GameSetup,
+    /// Side always shown at the bottom, or `None` to follow the game.
+    pub orientation_lock: Option<cozy_chess::Color>,
+    /// Where piece images are loaded from, or `None` for the default location.
+    pub assets_dir: Option<PathBuf>,
+    /// UCI engine picked in the app, started on launch unless `--engine` is given.
+    pub engine_path: Option<PathBuf>,
+    /// Folder every finished game is written to as PGN, or `None` to leave
+    /// saving to the user.
+    pub auto_save_dir: Option<PathBuf>,
+    /// Values chosen for UCI engine options, as (engine path, name, value).
+    pub engine_options: Vec<(String, String, String)>,
+    /// Positions saved by the user, after the built-in ones.
+    pub position_presets: Vec<PositionPreset>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            highlight_colors: HighlightColors::default(),
+            show_eval_number: false,
+            show_imbalance: false,
+            search_time_ms: 1000,
+            use_opening_book: true,
+            learner_hints: false,
+            captures_only: false,
+            input_mode: InputMode::Both,
+            promotion_behavior: PromotionBehavior::Ask,
+            last_promotion: Piece::Queen,
+            show_checking_moves: false,
+            show_defended: false,
+            last_move_style: LastMoveStyle::Squares,
+            animations: true,
+            animation_speed: 1.0,
+            move_trail: false,
+            move_trail_length: 3,
+            panel_split: 0.45,
+            board_margin: 20.0,
+            sound_enabled: true,
+            move_sounds: MoveSounds::Always,
+            show_castling_markers: false,
+            show_cursor_square: false,
+            teaching_coordinates: false,
+            coordinate_color: Color::from_rgb(0.1, 0.1, 0.1),
+            ghost_arrows: false,
+            ghost_arrow_filter: PieceFilter::All,
+            threat_preview: false,
+            blunder_warning: false,
+            blunder_threshold: 200,
+            shredder_fen: false,
+            pgn_clock_annotations: true,
+            game_setup: GameSetup::default(),
+            orientation_lock: None,
+            assets_dir: None,
+            engine_path: None,
+            auto_save_dir: None,
+            engine_options: Vec::new(),
+            position_presets: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let mut settings = Settings::default();
+
+        let Ok(contents) = fs::read_to_string(SETTINGS_PATH) else {
+            return settings;
+        };
+
+        for line in contents.lines() {
+            if let Some((key, value)) = line.split_once('=') {
+                settings.apply(key.trim(), value.trim());
+            }
+        }
+
+        settings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut contents = String::new();
+        for (key, value) in self.entries() {
+            contents += &format!("{key} = {value}\n");
+        }
+        fs::write(SETTINGS_PATH, contents)
+    }
+
+    /// Remembers `value` for the option `name` of the engine at `path`.
+    pub fn set_engine_option(&mut self, path: &str, name: &str, value: &str) {
+        self.engine_options.retain(|(p, n, _)| p != path || n != name);
+        self.engine_options.push((path.to_owned(), name.to_owned(), value.to_owned()));
+    }
+
+    /// Every preset, built-in ones first.
+    pub fn presets(&self) -> Vec<PositionPreset> {
+        let mut presets = PositionPreset::built_in();
+        presets.extend(self.position_presets.iter().cloned());
+        presets
+    }
+
+    /// Saves `fen` as `name`, replacing a saved preset of the same name.
+    pub fn save_preset(&mut self, name: &str, fen: &str) {
+        self.position_presets.retain(|preset| preset.name != name);
+        self.position_presets.push(PositionPreset::new(name, fen));
+    }
+
+    fn entries(&self) -> Vec<(&'static str, String)> {
+        let colors = &self.highlight_colors;
+        let mut entries = vec![
+            ("highlight.selection", color_to_hex(colors.selection)),
+            ("highlight.selected_outline", color_to_hex(colors.selected_outline)),
+            ("highlight.illegal_hint", color_to_hex(colors.illegal_hint)),
+            ("highlight.last_move", color_to_hex(colors.last_move)),
+            ("highlight.check", color_to_hex(colors.check)),
+            ("highlight.wrong_move", color_to_hex(colors.wrong_move)),
+            ("highlight.arrow", color_to_hex(colors.arrow)),
+            ("highlight.castling_marker", color_to_hex(colors.castling_marker)),
+            ("highlight.threat", color_to_hex(colors.threat)),
+            ("highlight.promotion_backdrop", color_to_hex(colors.promotion_backdrop)),
+            ("highlight.promotion_hover", color_to_hex(colors.promotion_hover)),
+            ("highlight.hover", color_to_hex(colors.hover)),
+            ("highlight.keyboard_cursor", color_to_hex(colors.keyboard_cursor)),
+            ("highlight.defended", color_to_hex(colors.defended)),
+            ("highlight.premove", color_to_hex(colors.premove)),
+            ("highlight.drop_legal", color_to_hex(colors.drop_legal)),
+            ("highlight.drop_illegal", color_to_hex(colors.drop_illegal)),
+            ("highlight.ghost_arrow", color_to_hex(colors.ghost_arrow)),
+            ("highlight.blunder_arrow", color_to_hex(colors.blunder_arrow)),
+            ("highlight.best_move_arrow", color_to_hex(colors.best_move_arrow)),
+            ("highlight.check_mark", color_to_hex(colors.check_mark)),
+            ("highlight.debug_overlay", color_to_hex(colors.debug_overlay)),
+            ("highlight.cursor_label", color_to_hex(colors.cursor_label)),
+            ("show_eval_number", self.show_eval_number.to_string()),
+            ("show_imbalance", self.show_imbalance.to_string()),
+            ("search_time_ms", self.search_time_ms.to_string()),
+            ("use_opening_book", self.use_opening_book.to_string()),
+            ("learner_hints", self.learner_hints.to_string()),
+            ("captures_only", self.captures_only.to_string()),
+            ("input_mode", self.input_mode.to_string()),
+            ("promotion_behavior", self.promotion_behavior.to_string()),
+            ("last_promotion", self.last_promotion.to_string()),
+            ("show_checking_moves", self.show_checking_moves.to_string()),
+            ("show_defended", self.show_defended.to_string()),
+            ("last_move_style", self.last_move_style.to_string()),
+            ("animations", self.animations.to_string()),
+            ("animation_speed", self.animation_speed.to_string()),
+            ("move_trail", self.move_trail.to_string()),
+            ("move_trail_length", self.move_trail_length.to_string()),
+            ("panel_split", self.panel_split.to_string()),
+            ("board_margin", self.board_margin.to_string()),
+            ("sound_enabled", self.sound_enabled.to_string()),
+            ("move_sounds", self.move_sounds.to_string()),
+            ("show_castling_markers", self.show_castling_markers.to_string()),
+            ("show_cursor_square", self.show_cursor_square.to_string()),
+            ("teaching_coordinates", self.teaching_coordinates.to_string()),
+            ("coordinate_color", color_to_hex(self.coordinate_color)),
+            ("ghost_arrows", self.ghost_arrows.to_string()),
+            ("ghost_arrow_filter", self.ghost_arrow_filter.to_string()),
+            ("threat_preview", self.threat_preview.to_string()),
+            ("blunder_warning", self.blunder_warning.to_string()),
+            ("blunder_threshold", self.blunder_threshold.to_string()),
+            ("shredder_fen", self.shredder_fen.to_string()),
+            ("pgn_clock_annotations", self.pgn_clock_annotations.to_string()),
+            ("new_game.clock", match self.game_setup.clock {
+                Some((base, increment)) => format!("{}+{}", base.as_secs(), increment.as_secs()),
+                None => "none".to_owned(),
+            }),
+            ("new_game.color", match self.game_setup.human_color {
+                cozy_chess::Color::White => "white".to_owned(),
+                cozy_chess::Color::Black => "black".to_owned(),
+            }),
+            ("new_game.opponent", self.game_setup.opponent.to_string()),
+            ("new_game.start", self.game_setup.start_fen.clone()),
+            ("new_game.mode", self.game_setup.mode.to_string()),
+            ("orientation_lock", match self.orientation_lock {
+                Some(cozy_chess::Color::White) => "white".to_owned(),
+                Some(cozy_chess::Color::Black) => "black".to_owned(),
+                None => "auto".to_owned(),
+            }),
+        ];
+
+        if let Some(assets_dir) = &self.assets_dir {
+            entries.push(("assets_dir", assets_dir.display().to_string()));
+        }
+        if let Some(engine_path) = &self.engine_path {
+            entries.push(("engine", engine_path.display().to_string()));
+        }
+        if let Some(auto_save_dir) = &self.auto_save_dir {
+            entries.push(("auto_save_dir", auto_save_dir.display().to_string()));
+        }
+        for (path, name, value) in &self.engine_options {
+            entries.push(("engine_option", format!("{path}|{name}|{value}")));
+        }
+        // the FEN goes first since it never holds a `|` and the name might
+        for preset in &self.position_presets {
+            entries.push(("position_preset", format!("{}|{}", preset.fen, preset.name)));
+        }
+
+        entries
+    }
+
+    // unknown keys and malformed values are ignored so old files keep loading
+    fn apply(&mut self, key: &str, value: &str) {
+        let colors = &mut self.highlight_colors;
+        match key {
+            "highlight.selection" => set_color(&mut colors.selection, value),
+            "highlight.selected_outline" => set_color(&mut colors.selected_outline, value),
+            "highlight.illegal_hint" => set_color(&mut colors.illegal_hint, value),
+            "highlight.last_move" => set_color(&mut colors.last_move, value),
+            "highlight.check" => set_color(&mut colors.check, value),
+            "highlight.wrong_move" => set_color(&mut colors.wrong_move, value),
+            "highlight.arrow" => set_color(&mut colors.arrow, value),
+            "highlight.castling_marker" => set_color(&mut colors.castling_marker, value),
+            "highlight.threat" => set_color(&mut colors.threat, value),
+            "highlight.promotion_backdrop" => set_color(&mut colors.promotion_backdrop, value),
+            "highlight.promotion_hover" => set_color(&mut colors.promotion_hover, value),
+            "highlight.hover" => set_color(&mut colors.hover, value),
+            "highlight.keyboard_cursor" => set_color(&mut colors.keyboard_cursor, value),
+            "highlight.defended" => set_color(&mut colors.defended, value),
+            "highlight.premove" => set_color(&mut colors.premove, value),
+            "highlight.drop_legal" => set_color(&mut colors.drop_legal, value),
+            "highlight.drop_illegal" => set_color(&mut colors.drop_illegal, value),
+            "highlight.ghost_arrow" => set_color(&mut colors.ghost_arrow, value),
+            "highlight.blunder_arrow" => set_color(&mut colors.blunder_arrow, value),
+            "highlight.best_move_arrow" => set_color(&mut colors.best_move_arrow, value),
+            "highlight.check_mark" => set_color(&mut colors.check_mark, value),
+            "highlight.debug_overlay" => set_color(&mut colors.debug_overlay, value),
+            "highlight.cursor_label" => set_color(&mut colors.cursor_label, value),
+            "show_eval_number" => set_parsed(&mut self.show_eval_number, value),
+            "show_imbalance" => set_parsed(&mut self.show_imbalance, value),
+            "search_time_ms" => set_parsed(&mut self.search_time_ms, value),
+            "use_opening_book" => set_parsed(&mut self.use_opening_book, value),
+            "learner_hints" => set_parsed(&mut self.learner_hints, value),
+            "captures_only" => set_parsed(&mut self.captures_only, value),
+            "input_mode" => set_parsed(&mut self.input_mode, value),
+            "promotion_behavior" => set_parsed(&mut self.promotion_behavior, value),
+            "last_promotion" => set_parsed(&mut self.last_promotion, value),
+            "show_checking_moves" => set_parsed(&mut self.show_checking_moves, value),
+            "show_defended" => set_parsed(&mut self.show_defended, value),
+            "last_move_style" => set_parsed(&mut self.last_move_style, value),
+            "animations" => set_parsed(&mut self.animations, value),
+            "animation_speed" => set_parsed(&mut self.animation_speed, value),
+            "move_trail" => set_parsed(&mut self.move_trail, value),
+            "move_trail_length" => set_parsed(&mut self.move_trail_length, value),
+            "panel_split" => set_parsed(&mut self.panel_split, value),
+            "board_margin" => set_parsed(&mut self.board_margin, value),
+            "sound_enabled" => set_parsed(&mut self.sound_enabled, value),
+            "move_sounds" => set_parsed(&mut self.move_sounds, value),
+            "show_castling_markers" => set_parsed(&mut self.show_castling_markers, value),
+            "show_cursor_square" => set_parsed(&mut self.show_cursor_square, value),
+            "teaching_coordinates" => set_parsed(&mut self.teaching_coordinates, value),
+            "coordinate_color" => set_color(&mut self.coordinate_color, value),
+            "ghost_arrows" => set_parsed(&mut self.ghost_arrows, value),
+            "ghost_arrow_filter" => set_parsed(&mut self.ghost_arrow_filter, value),
+            "threat_preview" => set_parsed(&mut self.threat_preview, value),
+            "blunder_warning" => set_parsed(&mut self.blunder_warning, value),
+            "blunder_threshold" => set_parsed(&mut self.blunder_threshold, value),
+            "shredder_fen" => set_parsed(&mut self.shredder_fen, value),
+            "pgn_clock_annotations" => set_parsed(&mut self.pgn_clock_annotations, value),
+            "new_game.clock" => {
+                let parsed = value.split_once('+')
+                    .and_then(|(base, increment)| Some((base.parse().ok()?, increment.parse().ok()?)));
+                match parsed {
+                    Some((base, increment)) => {
+                        self.game_setup.clock = Some((Duration::from_secs(base), Duration::from_secs(increment)));
+                    },
+                    None if value == "none" => self.game_setup.clock = None,
+                    None => {},
+                }
+            },
+            "new_game.color" => match value {
+                "white" => self.game_setup.human_color = cozy_chess::Color::White,
+                "black" => self.game_setup.human_color = cozy_chess::Color::Black,
+                _ => {}
+            },
+            "new_game.opponent" => set_parsed(&mut self.game_setup.opponent, value),
+            "new_game.start" => self.game_setup.start_fen = value.to_owned(),
+            "new_game.mode" => set_parsed(&mut self.game_setup.mode, value),
+            "orientation_lock" => match value {
+                "white" => self.orientation_lock = Some(cozy_chess::Color::White),
+                "black" => self.orientation_lock = Some(cozy_chess::Color::Black),
+                "auto" => self.orientation_lock = None,
+                _ => {}
+            },
+            "assets_dir" => self.assets_dir = Some(PathBuf::from(value)),
+            "engine" => self.engine_path = Some(PathBuf::from(value)),
+            "auto_save_dir" => self.auto_save_dir = Some(PathBuf::from(value)),
+            "engine_option" => {
+                let mut parts = value.splitn(3, '|');
+                if let (Some(path), Some(name), Some(value)) = (parts.next(), parts.next(), parts.next()) {
+                    self.set_engine_option(path, name, value);
+                }
+            },
+            "position_preset" => {
+                if let Some((fen, name)) = value.split_once('|') {
+                    self.save_preset(name, fen);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+fn set_color(target: &mut Color, value: &str) {
+    if let Some(color) = Color::parse(value) {
+        *target = color;
+    }
+}
+
+fn set_parsed<T: FromStr>(target: &mut T, value: &str) {
+    if let Ok(parsed) = value.parse() {
+        *target = parsed;
+    }
+}
+
+pub fn color_to_hex(color: Color) -> String {
+    let [r, g, b, a] = color.into_rgba8();
+    format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+}
@@ -0,0 +1,77 @@
+use std::time::{Duration, Instant};
+
+use rodio::source::SineWave;
+use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
+
+/// Moves closer together than this are treated as one burst.
+const BURST_WINDOW: Duration = Duration::from_millis(80);
+
+/// Sounds ordered by priority: within a burst a sound never replaces a more
+/// important one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SoundKind {
+    Move,
+    Capture,
+    Check,
+    LowTime,
+}
+
+impl SoundKind {
+    fn frequency(self) -> f32 {
+        match self {
+            SoundKind::Move => 440.0,
+            SoundKind::Capture => 330.0,
+            SoundKind::Check => 660.0,
+            SoundKind::LowTime => 880.0,
+        }
+    }
+}
+
+/// Short synthesized tones for board events.
+pub struct Audio {
+    // the stream has to stay alive for the handle to keep working
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    sink: Option<Sink>,
+    last_played: Option<(Instant, SoundKind)>,
+}
+
+impl Audio {
+    /// Falls back to silence when no output device is available.
+    pub fn new() -> Self {
+        Audio {
+            output: OutputStream::try_default().ok(),
+            sink: None,
+            last_played: None,
+        }
+    }
+
+    pub fn play(&mut self, kind: SoundKind) {
+        let now = Instant::now();
+
+        if let Some((at, last_kind)) = self.last_played {
+            if now.duration_since(at) < BURST_WINDOW && kind < last_kind {
+                return;
+            }
+        }
+
+        let Some((_, handle)) = &self.output else {
+            return;
+        };
+        let Ok(sink) = Sink::try_new(handle) else {
+            return;
+        };
+
+        // only the most recent sound of a burst is heard
+        if let Some(previous) = self.sink.take() {
+            previous.stop();
+        }
+
+        sink.append(
+            SineWave::new(kind.frequency())
+                .take_duration(Duration::from_millis(90))
+                .amplify(0.2),
+        );
+        self.sink = Some(sink);
+        self.last_played = Some((now, kind));
+    }
+}